@@ -1,9 +1,11 @@
 use std::ascii::AsciiExt;
 use std::fmt;
+use std::str::FromStr;
 
 use clap::ArgMatches;
 
 use error::{CliErrorKind, CliResult};
+use policy::Policy;
 
 trait BoolArg {
     fn parse_arg(&self) -> CliResult<bool>;
@@ -59,16 +61,24 @@ arg_enum!{
 }
 
 impl DotColor {
-    fn write(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    /// Returns the color's name, or `None` for the default (black).
+    pub fn name(&self) -> Option<&'static str> {
         match *self {
-            DotColor::Blue => write!(f, ",color=blue"),
-            DotColor::Green => write!(f, ",color=green"),
-            DotColor::Red => write!(f, ",color=red"),
-            DotColor::Yellow => write!(f, ",color=yellow"),
-            DotColor::White => write!(f, ",color=white"),
-            DotColor::Black => Ok(()),
-            DotColor::Purple => write!(f, ",color=purple"),
-            DotColor::Orange => write!(f, ",color=orange"),
+            DotColor::Blue => Some("blue"),
+            DotColor::Green => Some("green"),
+            DotColor::Red => Some("red"),
+            DotColor::Yellow => Some("yellow"),
+            DotColor::White => Some("white"),
+            DotColor::Black => None,
+            DotColor::Purple => Some("purple"),
+            DotColor::Orange => Some("orange"),
+        }
+    }
+
+    fn write(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, ",color={}", name),
+            None => Ok(()),
         }
     }
 }
@@ -94,8 +104,155 @@ impl DotShape {
     }
 }
 
+arg_enum!{
+    #[derive(Debug, Copy, Clone, PartialEq)]
+    pub enum EdgeLabel {
+        None,
+        Req
+    }
+}
+
+arg_enum!{
+    #[derive(Debug, Copy, Clone)]
+    pub enum RankDir {
+        TB,
+        LR,
+        BT,
+        RL
+    }
+}
+
+arg_enum!{
+    #[derive(Debug, Copy, Clone, PartialEq)]
+    pub enum OutputFormat {
+        Dot,
+        Tree,
+        Html,
+        Graphml,
+        Gexf,
+        Plantuml,
+        D2,
+        Csv,
+        Tsv
+    }
+}
+
+arg_enum!{
+    #[derive(Debug, Copy, Clone, PartialEq)]
+    pub enum External {
+        Full,
+        Muted,
+        Shallow
+    }
+}
+
+/// What to do with the dependency graph once it's built.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Command {
+    /// Render the graph
+    Graph,
+    /// Report crates with a denied license
+    Licenses,
+    /// Check the graph against a policy
+    Check,
+    /// Check that crates only depend on crates of the same or a lower layer
+    Layers,
+    /// Render the paths between two crates
+    Path,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Links {
+    None,
+    DocsRs,
+    CratesIo,
+}
+
+/// When to color the output written to stdout.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ColorWhen {
+    /// Only when stdout is a terminal
+    Auto,
+    Always,
+    Never,
+}
+
+impl FromStr for ColorWhen {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &*s.to_ascii_lowercase() {
+            "auto" => Ok(ColorWhen::Auto),
+            "always" => Ok(ColorWhen::Always),
+            "never" => Ok(ColorWhen::Never),
+            _ => Err("valid values: auto, always, never".to_owned()),
+        }
+    }
+}
+
+/// A per-node measure of how much of the graph leans on a crate.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Metric {
+    /// Number of crates depending directly on the crate
+    FanIn,
+    /// Number of crates the crate depends on, directly or not
+    Descendants,
+    /// Distance from the root
+    Depth,
+}
+
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &*s.to_ascii_lowercase() {
+            "fan-in" => Ok(Metric::FanIn),
+            "descendants" => Ok(Metric::Descendants),
+            "depth" => Ok(Metric::Depth),
+            _ => Err("valid values: fan-in, descendants, depth".to_owned()),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ColorBy {
+    Kind,
+    License,
+    /// A gradient from the lowest to the highest value of the metric
+    Metric(Metric),
+}
+
+impl FromStr for ColorBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &*s.to_ascii_lowercase() {
+            "kind" => Ok(ColorBy::Kind),
+            "license" => Ok(ColorBy::License),
+            _ => {
+                s.parse()
+                 .map(ColorBy::Metric)
+                 .map_err(|_| "valid values: kind, license, fan-in, descendants, depth".to_owned())
+            }
+        }
+    }
+}
+
+impl FromStr for Links {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &*s.to_ascii_lowercase() {
+            "none" => Ok(Links::None),
+            "docs.rs" => Ok(Links::DocsRs),
+            "crates.io" => Ok(Links::CratesIo),
+            _ => Err("valid values: none, docs.rs, crates.io".to_owned()),
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone)]
-pub struct DepStyle(DotShape, DotColor);
+pub struct DepStyle(pub DotShape, pub DotColor);
 
 impl fmt::Display for DepStyle {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -105,7 +262,7 @@ impl fmt::Display for DepStyle {
 }
 
 #[derive(Debug, Copy, Clone)]
-pub struct LineStyle(DotLineShape, DotColor);
+pub struct LineStyle(pub DotLineShape, pub DotColor);
 
 impl fmt::Display for LineStyle {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -116,57 +273,635 @@ impl fmt::Display for LineStyle {
 
 
 #[derive(Debug)]
-pub struct Config<'a> {
-    pub lock_file: &'a str,
-    pub manifest_file: &'a str,
-    pub dot_file: Option<&'a str>,
+pub struct Config {
+    pub command: Command,
+    pub lock_file: String,
+    pub manifest_file: String,
+    pub metadata: Option<String>,
+    pub vendor_dir: Option<String>,
+    pub registry_sources: bool,
+    pub dot_file: Option<String>,
+    pub format: OutputFormat,
+    pub nodes_file: Option<String>,
+    pub output: Option<String>,
     pub dev_lines: LineStyle,
     pub build_lines: LineStyle,
     pub optional_lines: LineStyle,
+    pub unknown_lines: LineStyle,
     pub dev_deps: bool,
     pub build_deps: bool,
     pub optional_deps: bool,
+    pub unknown_deps: bool,
     pub build_style: DepStyle,
     pub dev_style: DepStyle,
     pub optional_style: DepStyle,
+    pub unknown_style: DepStyle,
     pub include_vers: bool,
+    pub reduce: bool,
+    pub merge_versions: bool,
+    pub group_internal: bool,
+    pub watch: bool,
+    pub external: External,
+    pub edge_labels: EdgeLabel,
+    pub links: Links,
+    pub color: ColorWhen,
+    pub color_by: ColorBy,
+    pub size_by: Option<Metric>,
+    pub license_colors: Vec<(String, String)>,
+    pub deny_licenses: Vec<String>,
+    pub highlight: Vec<String>,
+    pub policy: Policy,
+    pub path_from: String,
+    pub path_to: String,
+    pub all_paths: bool,
+    pub path_limit: usize,
+    pub full_graph: bool,
+    pub rankdir: Option<RankDir>,
+    pub title: Option<String>,
+    pub graph_attrs: Vec<(String, String)>,
+    pub node_attrs: Vec<(String, String)>,
+    pub edge_attrs: Vec<(String, String)>,
 }
 
-impl<'a> Config<'a> {
+impl Config {
+    /// Whether the license of each crate is looked up, which only happens when it's needed
+    /// (or comes for free with `cargo metadata`).
+    pub fn reads_licenses(&self) -> bool {
+        self.color_by == ColorBy::License || self.command == Command::Licenses ||
+        self.metadata.is_some()
+    }
+
     #[cfg_attr(feature = "lints", allow(cyclomatic_complexity))]
-    pub fn from_matches(m: &'a ArgMatches) -> CliResult<Self> {
-        Ok(Config {
-            lock_file: m.value_of("lock-file").unwrap_or("Cargo.lock"),
-            manifest_file: m.value_of("manifest-file").unwrap_or("Cargo.toml"),
-            dot_file: m.value_of("dot-file"),
-            dev_deps: try!(m.value_of("dev-deps").unwrap_or("false").parse_arg()),
-            build_deps: try!(m.value_of("build-deps").unwrap_or("true").parse_arg()),
-            optional_deps: try!(m.value_of("optional-deps").unwrap_or("true").parse_arg()),
-            build_lines: LineStyle(value_t!(m.value_of("build-line-style"), DotLineShape)
+    pub fn from_matches(m: &ArgMatches) -> CliResult<Self> {
+        let format = value_t!(m.value_of("format"), OutputFormat).unwrap_or(OutputFormat::Dot);
+        let mut b = ConfigBuilder::new()
+            .dev_deps(try!(m.value_of("dev-deps").unwrap_or("false").parse_arg()))
+            .build_deps(try!(m.value_of("build-deps").unwrap_or("true").parse_arg()))
+            .optional_deps(try!(m.value_of("optional-deps").unwrap_or("true").parse_arg()))
+            .unknown_deps(try!(m.value_of("unknown-deps").unwrap_or("true").parse_arg()))
+            .build_lines(LineStyle(value_t!(m.value_of("build-line-style"), DotLineShape)
                                        .unwrap_or(DotLineShape::Solid),
                                    value_t!(m.value_of("build-line-color"), DotColor)
-                                       .unwrap_or(DotColor::Black)),
-            optional_lines: LineStyle(value_t!(m.value_of("optional-line-style"), DotLineShape)
+                                       .unwrap_or(DotColor::Black)))
+            .optional_lines(LineStyle(value_t!(m.value_of("optional-line-style"), DotLineShape)
                                           .unwrap_or(DotLineShape::Solid),
                                       value_t!(m.value_of("optional-line-color"), DotColor)
-                                          .unwrap_or(DotColor::Black)),
-            dev_lines: LineStyle(value_t!(m.value_of("dev-line-style"), DotLineShape)
+                                          .unwrap_or(DotColor::Black)))
+            .dev_lines(LineStyle(value_t!(m.value_of("dev-line-style"), DotLineShape)
                                      .unwrap_or(DotLineShape::Solid),
                                  value_t!(m.value_of("dev-line-color"), DotColor)
-                                     .unwrap_or(DotColor::Black)),
-            build_style: DepStyle(value_t!(m.value_of("build-shape"), DotShape)
+                                     .unwrap_or(DotColor::Black)))
+            .unknown_lines(LineStyle(value_t!(m.value_of("unknown-line-style"), DotLineShape)
+                                         .unwrap_or(DotLineShape::Solid),
+                                     value_t!(m.value_of("unknown-line-color"), DotColor)
+                                         .unwrap_or(DotColor::Black)))
+            .build_style(DepStyle(value_t!(m.value_of("build-shape"), DotShape)
                                       .unwrap_or(DotShape::Round),
                                   value_t!(m.value_of("build-color"), DotColor)
-                                      .unwrap_or(DotColor::Black)),
-            optional_style: DepStyle(value_t!(m.value_of("optional-shape"), DotShape)
+                                      .unwrap_or(DotColor::Black)))
+            .optional_style(DepStyle(value_t!(m.value_of("optional-shape"), DotShape)
                                          .unwrap_or(DotShape::Round),
                                      value_t!(m.value_of("optional-color"), DotColor)
-                                         .unwrap_or(DotColor::Black)),
-            dev_style: DepStyle(value_t!(m.value_of("dev-shape"), DotShape)
+                                         .unwrap_or(DotColor::Black)))
+            .dev_style(DepStyle(value_t!(m.value_of("dev-shape"), DotShape)
                                     .unwrap_or(DotShape::Round),
                                 value_t!(m.value_of("dev-color"), DotColor)
-                                    .unwrap_or(DotColor::Black)),
-            include_vers: m.is_present("include-versions"),
-        })
+                                    .unwrap_or(DotColor::Black)))
+            .unknown_style(DepStyle(value_t!(m.value_of("unknown-shape"), DotShape)
+                                        .unwrap_or(DotShape::Round),
+                                    value_t!(m.value_of("unknown-color"), DotColor)
+                                        .unwrap_or(DotColor::Black)))
+            .include_vers(m.is_present("include-versions"))
+            .reduce(m.is_present("reduce"))
+            .merge_versions(m.is_present("merge-versions"))
+            .group_internal(m.is_present("group-internal"))
+            .watch(m.is_present("watch"))
+            .registry_sources(m.is_present("registry-sources"))
+            .external(value_t!(m.value_of("external"), External).unwrap_or(External::Full))
+            .edge_labels(value_t!(m.value_of("edge-labels"), EdgeLabel)
+                             .unwrap_or(EdgeLabel::None))
+            .links(value_t!(m.value_of("links"), Links).unwrap_or(Links::None))
+            .color(value_t!(m.value_of("color"), ColorWhen).unwrap_or(ColorWhen::Auto))
+            .format(format)
+            .color_by(value_t!(m.value_of("color-by"), ColorBy).unwrap_or(ColorBy::Kind));
+        if let Ok(metric) = value_t!(m.value_of("size-by"), Metric) {
+            b = b.size_by(metric);
+        }
+        for pattern in m.values_of("highlight").into_iter().flat_map(|v| v) {
+            b = b.highlight(pattern);
+        }
+        for attr in m.values_of("license-color").into_iter().flat_map(|v| v) {
+            let (license, color) = split_attr(attr);
+            b = b.license_color(license, color);
+        }
+        if let Some(m) = m.subcommand_matches("licenses") {
+            b = b.command(Command::Licenses);
+            for license in m.values_of("deny").into_iter().flat_map(|v| v) {
+                b = b.deny_license(license);
+            }
+        }
+        if let Some(m) = m.subcommand_matches("check") {
+            b = b.command(Command::Check)
+                 .policy(try!(Policy::from_file(m.value_of("config").unwrap())));
+        }
+        if let Some(m) = m.subcommand_matches("layers") {
+            let policy = try!(Policy::from_file(m.value_of("config").unwrap()));
+            if policy.layers.is_empty() {
+                return Err(From::from(CliErrorKind::Generic("the policy file doesn't declare \
+                                                             any [layers]"
+                                                                .to_owned())));
+            }
+            b = b.command(Command::Layers).policy(policy);
+        }
+        if let Some(m) = m.subcommand_matches("path") {
+            b = b.command(Command::Path)
+                 .path(m.value_of("FROM").unwrap(), m.value_of("TO").unwrap())
+                 .all_paths(m.is_present("all"))
+                 .path_limit(value_t!(m.value_of("limit"), usize).unwrap_or(100))
+                 .full_graph(m.is_present("full"));
+        }
+        if let Ok(dir) = value_t!(m.value_of("rankdir"), RankDir) {
+            b = b.rankdir(dir);
+        }
+        if let Some(title) = m.value_of("title") {
+            b = b.title(title);
+        }
+        for attr in m.values_of("graph-attr").into_iter().flat_map(|v| v) {
+            let (key, value) = split_attr(attr);
+            b = b.graph_attr(key, value);
+        }
+        for attr in m.values_of("node-attr").into_iter().flat_map(|v| v) {
+            let (key, value) = split_attr(attr);
+            b = b.node_attr(key, value);
+        }
+        for attr in m.values_of("edge-attr").into_iter().flat_map(|v| v) {
+            let (key, value) = split_attr(attr);
+            b = b.edge_attr(key, value);
+        }
+        if let Some(f) = m.value_of("lock-file") {
+            b = b.lock_file(f);
+        }
+        if let Some(f) = m.value_of("manifest-file") {
+            b = b.manifest_file(f);
+        }
+        if let Some(f) = m.value_of("metadata") {
+            b = b.metadata(f);
+        }
+        if let Some(dir) = m.value_of("vendor-dir") {
+            b = b.vendor_dir(dir);
+        }
+        if let Some(f) = m.value_of("dot-file") {
+            b = b.dot_file(f);
+        }
+        if let Some(f) = m.value_of("output") {
+            if format != OutputFormat::Dot {
+                return Err(From::from(CliErrorKind::Generic(format!("--output renders the DOT \
+                                                                     graph with `dot`, it can't \
+                                                                     be used with --format {}",
+                                                                    m.value_of("format")
+                                                                     .unwrap_or("")))));
+            }
+            b = b.output(f);
+        }
+        if let Some(f) = m.value_of("nodes-file") {
+            if format != OutputFormat::Csv && format != OutputFormat::Tsv {
+                return Err(From::from(CliErrorKind::Generic("--nodes-file needs --format csv \
+                                                             or --format tsv"
+                                                                .to_owned())));
+            }
+            b = b.nodes_file(f);
+        }
+        Ok(b.build())
+    }
+}
+
+/// Splits a `key=value` DOT attribute given on the command line.
+fn split_attr(attr: &str) -> (&str, &str) {
+    let mut parts = attr.splitn(2, '=');
+    (parts.next().unwrap_or(""), parts.next().unwrap_or(""))
+}
+
+/// Builds a `Config` without going through the command line. Every field starts
+/// out with the same default the CLI uses.
+#[derive(Debug)]
+pub struct ConfigBuilder {
+    cfg: Config,
+}
+
+impl Default for ConfigBuilder {
+    fn default() -> Self {
+        ConfigBuilder {
+            cfg: Config {
+                command: Command::Graph,
+                lock_file: "Cargo.lock".to_owned(),
+                manifest_file: "Cargo.toml".to_owned(),
+                metadata: None,
+                vendor_dir: None,
+                registry_sources: false,
+                dot_file: None,
+                format: OutputFormat::Dot,
+                nodes_file: None,
+                output: None,
+                dev_lines: LineStyle(DotLineShape::Solid, DotColor::Black),
+                build_lines: LineStyle(DotLineShape::Solid, DotColor::Black),
+                optional_lines: LineStyle(DotLineShape::Solid, DotColor::Black),
+                unknown_lines: LineStyle(DotLineShape::Solid, DotColor::Black),
+                dev_deps: false,
+                build_deps: true,
+                optional_deps: true,
+                unknown_deps: true,
+                build_style: DepStyle(DotShape::Round, DotColor::Black),
+                dev_style: DepStyle(DotShape::Round, DotColor::Black),
+                optional_style: DepStyle(DotShape::Round, DotColor::Black),
+                unknown_style: DepStyle(DotShape::Round, DotColor::Black),
+                include_vers: false,
+                reduce: false,
+                merge_versions: false,
+                group_internal: false,
+                watch: false,
+                external: External::Full,
+                edge_labels: EdgeLabel::None,
+                links: Links::None,
+                color: ColorWhen::Auto,
+                color_by: ColorBy::Kind,
+                size_by: None,
+                license_colors: vec![],
+                deny_licenses: vec![],
+                highlight: vec![],
+                policy: Policy::default(),
+                path_from: String::new(),
+                path_to: String::new(),
+                all_paths: false,
+                path_limit: 100,
+                full_graph: false,
+                rankdir: None,
+                title: None,
+                graph_attrs: vec![],
+                node_attrs: vec![],
+                edge_attrs: vec![],
+            },
+        }
+    }
+}
+
+impl ConfigBuilder {
+    pub fn new() -> Self {
+        ConfigBuilder::default()
+    }
+
+    pub fn command(mut self, command: Command) -> Self {
+        self.cfg.command = command;
+        self
+    }
+
+    pub fn lock_file<S: Into<String>>(mut self, file: S) -> Self {
+        self.cfg.lock_file = file.into();
+        self
+    }
+
+    pub fn manifest_file<S: Into<String>>(mut self, file: S) -> Self {
+        self.cfg.manifest_file = file.into();
+        self
+    }
+
+    pub fn metadata<S: Into<String>>(mut self, file: S) -> Self {
+        self.cfg.metadata = Some(file.into());
+        self
+    }
+
+    /// Reads the manifests of crates vendored in `dir` (by `cargo vendor`) to find exactly how
+    /// each crate declares its dependencies.
+    pub fn vendor_dir<S: Into<String>>(mut self, dir: S) -> Self {
+        self.cfg.vendor_dir = Some(dir.into());
+        self
+    }
+
+    /// Also looks for the manifests of crates in the cargo registry sources (after the vendor
+    /// directory). What's found there depends on what was built before, so it's opt-in.
+    pub fn registry_sources(mut self, read: bool) -> Self {
+        self.cfg.registry_sources = read;
+        self
+    }
+
+    pub fn dot_file<S: Into<String>>(mut self, file: S) -> Self {
+        self.cfg.dot_file = Some(file.into());
+        self
+    }
+
+    pub fn format(mut self, format: OutputFormat) -> Self {
+        self.cfg.format = format;
+        self
+    }
+
+    /// Also writes a table of the nodes to `file` for the CSV and TSV formats.
+    pub fn nodes_file<S: Into<String>>(mut self, file: S) -> Self {
+        self.cfg.nodes_file = Some(file.into());
+        self
+    }
+
+    /// Renders an image to `file` with GraphViz `dot` instead of writing the graph itself. The
+    /// image format is taken from the file's extension.
+    pub fn output<S: Into<String>>(mut self, file: S) -> Self {
+        self.cfg.output = Some(file.into());
+        self
+    }
+
+    pub fn dev_lines(mut self, style: LineStyle) -> Self {
+        self.cfg.dev_lines = style;
+        self
+    }
+
+    pub fn build_lines(mut self, style: LineStyle) -> Self {
+        self.cfg.build_lines = style;
+        self
+    }
+
+    pub fn optional_lines(mut self, style: LineStyle) -> Self {
+        self.cfg.optional_lines = style;
+        self
+    }
+
+    pub fn unknown_lines(mut self, style: LineStyle) -> Self {
+        self.cfg.unknown_lines = style;
+        self
+    }
+
+    pub fn dev_deps(mut self, include: bool) -> Self {
+        self.cfg.dev_deps = include;
+        self
+    }
+
+    pub fn build_deps(mut self, include: bool) -> Self {
+        self.cfg.build_deps = include;
+        self
+    }
+
+    pub fn optional_deps(mut self, include: bool) -> Self {
+        self.cfg.optional_deps = include;
+        self
+    }
+
+    pub fn unknown_deps(mut self, include: bool) -> Self {
+        self.cfg.unknown_deps = include;
+        self
+    }
+
+    pub fn build_style(mut self, style: DepStyle) -> Self {
+        self.cfg.build_style = style;
+        self
+    }
+
+    pub fn dev_style(mut self, style: DepStyle) -> Self {
+        self.cfg.dev_style = style;
+        self
+    }
+
+    pub fn optional_style(mut self, style: DepStyle) -> Self {
+        self.cfg.optional_style = style;
+        self
+    }
+
+    pub fn unknown_style(mut self, style: DepStyle) -> Self {
+        self.cfg.unknown_style = style;
+        self
+    }
+
+    pub fn include_vers(mut self, include: bool) -> Self {
+        self.cfg.include_vers = include;
+        self
+    }
+
+    /// Removes edges that are implied by a longer path (a transitive reduction).
+    pub fn reduce(mut self, reduce: bool) -> Self {
+        self.cfg.reduce = reduce;
+        self
+    }
+
+    /// Collapses every version of a crate into a single node.
+    pub fn merge_versions(mut self, merge: bool) -> Self {
+        self.cfg.merge_versions = merge;
+        self
+    }
+
+    /// Draws first-party (path) crates bold and in their own cluster.
+    pub fn group_internal(mut self, group: bool) -> Self {
+        self.cfg.group_internal = group;
+        self
+    }
+
+    pub fn watch(mut self, watch: bool) -> Self {
+        self.cfg.watch = watch;
+        self
+    }
+
+    /// How third-party crates are drawn: in full, muted, or only the direct dependencies of
+    /// first-party crates.
+    pub fn external(mut self, external: External) -> Self {
+        self.cfg.external = external;
+        self
+    }
+
+    pub fn edge_labels(mut self, labels: EdgeLabel) -> Self {
+        self.cfg.edge_labels = labels;
+        self
+    }
+
+    pub fn links(mut self, links: Links) -> Self {
+        self.cfg.links = links;
+        self
+    }
+
+    /// Sets when the output written to stdout is colored.
+    pub fn color(mut self, color: ColorWhen) -> Self {
+        self.cfg.color = color;
+        self
+    }
+
+    pub fn color_by(mut self, color_by: ColorBy) -> Self {
+        self.cfg.color_by = color_by;
+        self
+    }
+
+    /// Sets the metric that decides the size of nodes.
+    pub fn size_by(mut self, metric: Metric) -> Self {
+        self.cfg.size_by = Some(metric);
+        self
+    }
+
+    /// Sets the fill color used for a license expression with `--color-by license`.
+    pub fn license_color<L: Into<String>, C: Into<String>>(mut self, license: L, color: C) -> Self {
+        self.cfg.license_colors.push((license.into(), color.into()));
+        self
+    }
+
+    /// Adds a pattern for crates to highlight, where `*` matches any run of characters.
+    pub fn highlight<S: Into<String>>(mut self, pattern: S) -> Self {
+        self.cfg.highlight.push(pattern.into());
+        self
+    }
+
+    /// Adds a license that crates must not use.
+    pub fn deny_license<S: Into<String>>(mut self, license: S) -> Self {
+        self.cfg.deny_licenses.push(license.into());
+        self
+    }
+
+    pub fn policy(mut self, policy: Policy) -> Self {
+        self.cfg.policy = policy;
+        self
+    }
+
+    /// Sets the crates to find the paths between, each a name or `name@version`.
+    pub fn path<S: Into<String>, T: Into<String>>(mut self, from: S, to: T) -> Self {
+        self.cfg.path_from = from.into();
+        self.cfg.path_to = to.into();
+        self
+    }
+
+    pub fn all_paths(mut self, all: bool) -> Self {
+        self.cfg.all_paths = all;
+        self
+    }
+
+    pub fn path_limit(mut self, limit: usize) -> Self {
+        self.cfg.path_limit = limit;
+        self
+    }
+
+    pub fn full_graph(mut self, full: bool) -> Self {
+        self.cfg.full_graph = full;
+        self
+    }
+
+    pub fn rankdir(mut self, dir: RankDir) -> Self {
+        self.cfg.rankdir = Some(dir);
+        self
+    }
+
+    pub fn title<S: Into<String>>(mut self, title: S) -> Self {
+        self.cfg.title = Some(title.into());
+        self
+    }
+
+    /// Adds a graph-wide DOT attribute, such as `splines=ortho`.
+    pub fn graph_attr<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.cfg.graph_attrs.push((key.into(), value.into()));
+        self
+    }
+
+    /// Adds a default DOT attribute for every node, such as `fontname=Helvetica`.
+    pub fn node_attr<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.cfg.node_attrs.push((key.into(), value.into()));
+        self
+    }
+
+    /// Adds a default DOT attribute for every edge, such as `arrowsize=0.5`.
+    pub fn edge_attr<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.cfg.edge_attrs.push((key.into(), value.into()));
+        self
+    }
+
+    pub fn build(self) -> Config {
+        self.cfg
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builder_starts_with_the_cli_defaults() {
+        let cfg = ConfigBuilder::new().build();
+        assert_eq!(cfg.command, Command::Graph);
+        assert_eq!(cfg.lock_file, "Cargo.lock");
+        assert_eq!(cfg.manifest_file, "Cargo.toml");
+        assert_eq!(cfg.format, OutputFormat::Dot);
+        assert!(!cfg.dev_deps);
+        assert!(cfg.build_deps && cfg.optional_deps && cfg.unknown_deps);
+        assert!(!cfg.include_vers);
+        assert_eq!(cfg.path_limit, 100);
+        assert_eq!(cfg.color, ColorWhen::Auto);
+    }
+
+    #[test]
+    fn builder_sets_fields() {
+        let cfg = ConfigBuilder::new()
+                      .lock_file("a/Cargo.lock")
+                      .manifest_file("a/Cargo.toml")
+                      .dev_deps(true)
+                      .include_vers(true)
+                      .dot_file("deps.dot")
+                      .node_attr("fontname", "Helvetica")
+                      .node_attr("shape", "box")
+                      .build();
+        assert_eq!(cfg.lock_file, "a/Cargo.lock");
+        assert_eq!(cfg.manifest_file, "a/Cargo.toml");
+        assert!(cfg.dev_deps);
+        assert!(cfg.include_vers);
+        assert_eq!(cfg.dot_file, Some("deps.dot".to_owned()));
+        assert_eq!(cfg.node_attrs,
+                   [("fontname".to_owned(), "Helvetica".to_owned()),
+                    ("shape".to_owned(), "box".to_owned())]);
+    }
+
+    /// Parses `cargo graph <args>` the way the binary does.
+    fn from_args(args: &[&str]) -> Config {
+        let m = ::build_cli().get_matches_from(["cargo", "graph"].iter().chain(args));
+        Config::from_matches(m.subcommand_matches("graph").unwrap()).unwrap()
+    }
+
+    fn assert_same(l: &Config, r: &Config) {
+        assert_eq!(format!("{:?}", l), format!("{:?}", r));
+    }
+
+    #[test]
+    fn from_matches_without_args_matches_the_builder_defaults() {
+        assert_same(&from_args(&[]), &ConfigBuilder::new().build());
+    }
+
+    #[test]
+    fn from_matches_matches_the_builder() {
+        let cfg = from_args(&["--dev-deps", "yes", "--optional-deps", "false", "-I", "--reduce",
+                              "--registry-sources", "--dot-file", "deps.dot", "--build-line-style", "dashed",
+                              "--optional-shape", "box", "--optional-color", "red",
+                              "--external", "shallow", "--rankdir", "LR", "--title", "Deps",
+                              "--node-attr", "fontname=Helvetica", "--node-attr", "shape=box",
+                              "path", "app", "libc@0.2.0", "--all"]);
+        let built = ConfigBuilder::new()
+                        .dev_deps(true)
+                        .optional_deps(false)
+                        .include_vers(true)
+                        .reduce(true)
+                        .registry_sources(true)
+                        .dot_file("deps.dot")
+                        .build_lines(LineStyle(DotLineShape::Dashed, DotColor::Black))
+                        .optional_style(DepStyle(DotShape::Box, DotColor::Red))
+                        .external(External::Shallow)
+                        .rankdir(RankDir::LR)
+                        .title("Deps")
+                        .node_attr("fontname", "Helvetica")
+                        .node_attr("shape", "box")
+                        .command(Command::Path)
+                        .path("app", "libc@0.2.0")
+                        .all_paths(true)
+                        .build();
+        assert_same(&cfg, &built);
+    }
+
+    #[test]
+    fn parses_metrics_and_color_by() {
+        assert_eq!("fan-in".parse::<Metric>(), Ok(Metric::FanIn));
+        assert_eq!("Depth".parse::<ColorBy>(), Ok(ColorBy::Metric(Metric::Depth)));
+        assert_eq!("license".parse::<ColorBy>(), Ok(ColorBy::License));
+        assert!("size".parse::<ColorBy>().is_err());
+    }
+
+    #[test]
+    fn parses_color_when() {
+        assert_eq!("auto".parse::<ColorWhen>(), Ok(ColorWhen::Auto));
+        assert_eq!("Always".parse::<ColorWhen>(), Ok(ColorWhen::Always));
+        assert_eq!("never".parse::<ColorWhen>(), Ok(ColorWhen::Never));
+        assert!("yes".parse::<ColorWhen>().is_err());
     }
 }
@@ -0,0 +1,110 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use error::CliResult;
+use graph::DepGraph;
+
+impl<'c> DepGraph<'c> {
+    /// Renders the edges of the graph as a flat table, one row per edge, with fields separated
+    /// by `sep`. When a nodes file was requested, the nodes are written to it as a second table.
+    pub fn render_table_to<W: Write>(mut self, output: &mut W, sep: char) -> CliResult<()> {
+        debugln!("exec=render_table_to; sep={:?}", sep);
+        self.prepare();
+        try!(write_row(output,
+                       sep,
+                       &["parent", "parent_version", "child", "child_version", "edge_kind",
+                         "child_kind"]));
+        for ed in &self.edges {
+            let parent = &self.nodes[ed.0];
+            let child = &self.nodes[ed.1];
+            try!(write_row(output,
+                           sep,
+                           &[&parent.name,
+                             &parent.versions(),
+                             &child.name,
+                             &child.versions(),
+                             &ed.kind(&self).to_string(),
+                             &child.kind().to_string()]));
+        }
+
+        if let Some(ref file) = self.cfg.nodes_file {
+            let mut w = BufWriter::new(try!(File::create(file)));
+            try!(write_row(&mut w, sep, &["name", "version", "kind", "source", "duplicate"]));
+            for (i, dep) in self.nodes.iter().enumerate() {
+                try!(write_row(&mut w,
+                               sep,
+                               &[&dep.name,
+                                 &dep.versions(),
+                                 &dep.kind().to_string(),
+                                 dep.source.as_ref().map_or("", |s| &**s),
+                                 &self.is_duplicate(i).to_string()]));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes a single row, quoting CSV fields when needed. TSV fields can't be quoted, so any tabs
+/// or newlines in them are replaced with spaces.
+fn write_row<W: Write>(w: &mut W, sep: char, fields: &[&str]) -> io::Result<()> {
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            try!(write!(w, "{}", sep));
+        }
+        if sep == '\t' {
+            try!(write!(w, "{}", field.replace(|c| c == '\t' || c == '\n' || c == '\r', " ")));
+        } else if field.contains(|c| c == sep || c == '"' || c == '\n' || c == '\r') {
+            try!(write!(w, "\"{}\"", field.replace('"', "\"\"")));
+        } else {
+            try!(write!(w, "{}", field));
+        }
+    }
+    writeln!(w, "")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs::File;
+    use std::io::Read;
+
+    use config::ConfigBuilder;
+    use dep::DepKind;
+    use graph::tests::graph;
+
+    #[test]
+    fn csv_lists_edges_and_nodes() {
+        let nodes = env::temp_dir().join("cargo-graph-nodes.csv");
+        let cfg = ConfigBuilder::new().nodes_file(nodes.to_str().unwrap()).build();
+        let mut dg = graph(&cfg, &[("app", "a"), ("app", "b")]);
+        dg.nodes[2].is_build = false;
+        dg.nodes[2].is_dev = true;
+        dg.nodes[2].source = Some("git+https://example.com/b?rev=\"1,2\"".to_owned());
+        dg.edges[1].2 = DepKind::Dev;
+        let mut out = vec![];
+        dg.render_table_to(&mut out, ',').unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(),
+                   "parent,parent_version,child,child_version,edge_kind,child_kind\n\
+                    app,1.0.0,a,1.0.0,normal,normal\n\
+                    app,1.0.0,b,1.0.0,dev,dev\n");
+        let mut written = String::new();
+        File::open(&nodes).unwrap().read_to_string(&mut written).unwrap();
+        assert_eq!(written,
+                   "name,version,kind,source,duplicate\n\
+                    app,1.0.0,normal,,false\n\
+                    a,1.0.0,normal,,false\n\
+                    b,1.0.0,dev,\"git+https://example.com/b?rev=\"\"1,2\"\"\",false\n");
+    }
+
+    #[test]
+    fn tsv_replaces_tabs_and_newlines() {
+        let cfg = ConfigBuilder::new().build();
+        let mut dg = graph(&cfg, &[("app", "a@1.0.0\tbeta")]);
+        dg.nodes[0].name = "my\napp".to_owned();
+        let mut out = vec![];
+        dg.render_table_to(&mut out, '\t').unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(),
+                   "parent\tparent_version\tchild\tchild_version\tedge_kind\tchild_kind\n\
+                    my app\t1.0.0\ta\t1.0.0 beta\tnormal\tnormal\n");
+    }
+}
@@ -1,8 +1,10 @@
+use std::fmt;
 use std::io::{Result, Write};
 
-use config::Config;
+use config::{ColorBy, Config, DepStyle, External, Links};
+use license;
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum DepKind {
     Build,
     Dev,
@@ -10,10 +12,37 @@ pub enum DepKind {
     Unk,
 }
 
+impl DepKind {
+    /// Which kind wins when a crate depends on another in more than one way: a normal
+    /// dependency wins over an optional one, which wins over a dev one.
+    pub fn priority(&self) -> u8 {
+        match *self {
+            DepKind::Build => 3,
+            DepKind::Optional => 2,
+            DepKind::Dev => 1,
+            DepKind::Unk => 0,
+        }
+    }
+}
+
+impl fmt::Display for DepKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            // Build is a normal dependency, not a build-dependency
+            DepKind::Build => write!(f, "normal"),
+            DepKind::Dev => write!(f, "dev"),
+            DepKind::Optional => write!(f, "optional"),
+            DepKind::Unk => write!(f, "unknown"),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct DeclaredDep {
     pub name: String,
     pub kind: DepKind,
+    pub req: Option<String>,
+    pub path: Option<String>,
 }
 
 impl DeclaredDep {
@@ -21,18 +50,41 @@ impl DeclaredDep {
         DeclaredDep {
             name: name,
             kind: kind,
+            req: None,
+            path: None,
         }
     }
+
+    /// Sets the local path of a path dependency.
+    pub fn with_path(mut self, path: Option<String>) -> Self {
+        self.path = path;
+        self
+    }
+
+    /// Sets the version requirement (or git/path source) the dependency was declared with.
+    pub fn with_req(mut self, req: Option<String>) -> Self {
+        self.req = req;
+        self
+    }
 }
 
 #[derive(Debug, PartialEq)]
 pub struct ResolvedDep {
     pub name: String,
     pub ver: String,
+    pub source: Option<String>,
+    pub merged_vers: Vec<String>,
+    pub license: Option<String>,
     pub is_build: bool,
     pub is_optional: bool,
     pub is_dev: bool,
     pub force_write_ver: bool,
+    pub highlighted: bool,
+    pub dimmed: bool,
+    /// Scale of the node from 0 to 1, with `--size-by`
+    pub size: Option<f64>,
+    /// Fill color of the node on a gradient from 0 to 1, with `--color-by` a metric
+    pub heat: Option<f64>,
 }
 
 impl ResolvedDep {
@@ -40,10 +92,38 @@ impl ResolvedDep {
         ResolvedDep {
             name: name,
             ver: ver,
+            source: None,
+            merged_vers: vec![],
+            license: None,
             is_build: false,
             is_optional: false,
             is_dev: false,
             force_write_ver: false,
+            highlighted: false,
+            dimmed: false,
+            size: None,
+            heat: None,
+        }
+    }
+
+    /// Whether the crate is first-party, i.e. comes from a local path rather than a registry or
+    /// git repository.
+    pub fn is_local(&self) -> bool {
+        self.source.as_ref().map_or(true, |s| s.starts_with("path+"))
+    }
+
+    /// Whether several versions of this crate were merged into this one node.
+    pub fn is_merged(&self) -> bool {
+        !self.merged_vers.is_empty()
+    }
+
+    /// The version of the crate, or the list of versions if several were merged (ex. `0.7.3 |
+    /// 0.8.5`).
+    pub fn versions(&self) -> String {
+        if self.is_merged() {
+            self.merged_vers.join(" | ")
+        } else {
+            self.ver.clone()
         }
     }
 
@@ -59,16 +139,204 @@ impl ResolvedDep {
         }
     }
 
-    pub fn label<W: Write>(&self, w: &mut W, c: &Config) -> Result<()> {
-        let name = if self.force_write_ver || c.include_vers {
+    /// Returns where the crate should link to: its docs.rs or crates.io page for registry
+    /// crates, or its repository or source path otherwise.
+    pub fn url(&self, links: Links) -> Option<String> {
+        let source = match self.source {
+            Some(ref s) => s,
+            None => return None,
+        };
+        if source.starts_with("registry+") {
+            // A merged node has no single version to link to
+            let ver = if self.is_merged() { "" } else { &*self.ver };
+            match links {
+                Links::DocsRs => Some(format!("https://docs.rs/{}/{}", self.name, ver)),
+                Links::CratesIo => Some(format!("https://crates.io/crates/{}/{}", self.name, ver)),
+                Links::None => None,
+            }
+        } else if source.starts_with("git+") {
+            Some(source[4..].split(|c| c == '?' || c == '#').next().unwrap_or("").to_owned())
+        } else if source.starts_with("path+") {
+            Some(source[5..].to_owned())
+        } else {
+            None
+        }
+    }
+
+    /// A short description of the crate's version, source, and kind, and its license when
+    /// licenses were looked up.
+    pub fn tooltip(&self, c: &Config) -> String {
+        let source = match self.source {
+            Some(ref s) if s == "registry+https://github.com/rust-lang/crates.io-index" => "crates.io",
+            Some(ref s) => s.splitn(2, '+').nth(1).unwrap_or(s),
+            None => "local path",
+        };
+        let mut tooltip = format!("{} v{}\nsource: {}\nkind: {}",
+                                  self.name,
+                                  self.versions(),
+                                  source,
+                                  self.kind());
+        if c.reads_licenses() {
+            tooltip.push_str(&format!("\nlicense: {}",
+                                      self.license.as_ref().map_or("unknown", |l| &**l)));
+        }
+        tooltip
+    }
+
+    /// The text shown on the node: the name, plus the version when it's needed or requested.
+    pub fn display_name(&self, c: &Config) -> String {
+        if self.is_merged() {
+            format!("{} {}", self.name, self.versions())
+        } else if self.force_write_ver || c.include_vers {
             format!("{} v{}", self.name, self.ver)
         } else {
             self.name.clone()
-        };
+        }
+    }
+
+    pub fn style(&self, c: &Config) -> DepStyle {
         match self.kind() {
-            DepKind::Dev => writeln!(w, "[label={:?}{}];", name, c.dev_style),
-            DepKind::Optional => writeln!(w, "[label={:?}{}];", name, c.optional_style),
-            _ => writeln!(w, "[label={:?}{}];", name, c.build_style),
+            DepKind::Dev => c.dev_style,
+            DepKind::Optional => c.optional_style,
+            DepKind::Build => c.build_style,
+            DepKind::Unk => c.unknown_style,
+        }
+    }
+
+    pub fn label<W: Write>(&self, w: &mut W, c: &Config) -> Result<()> {
+        let name = self.display_name(c);
+        let links = match (c.links, self.url(c.links)) {
+            (Links::None, _) => String::new(),
+            (_, Some(url)) => format!(",URL={:?},tooltip={:?}", url, self.tooltip(c)),
+            (_, None) => format!(",tooltip={:?}", self.tooltip(c)),
+        };
+        // A double border flags nodes that stand for several versions
+        let merged = if self.is_merged() { ",peripheries=2" } else { "" };
+        let mut extra = String::new();
+        let mut styles = vec![];
+        let muted = !self.is_local() && c.external == External::Muted;
+        if self.is_local() && c.group_internal {
+            styles.push("bold");
+        }
+        let mut fill = None;
+        let mut denied = false;
+        if c.color_by == ColorBy::License {
+            let license = self.license.as_ref().map(|l| &**l);
+            fill = Some(format!("{:?}", license::color_for(license, &c.license_colors)));
+            denied = license.map_or(false, |l| license::is_denied(l, &c.deny_licenses));
+        }
+        if let Some(heat) = self.heat {
+            // From pale yellow to red, as an HSV color
+            fill = Some(format!("\"{:.3} {:.3} 1.000\"", 0.17 * (1.0 - heat), 0.15 + 0.85 * heat));
+        }
+        if let Some(fill) = fill {
+            styles.push("filled");
+            // Dimmed nodes lose their fill, so the highlighted ones stand out
+            extra.push_str(&format!(",fillcolor={}", if self.dimmed { "gray95" } else { &*fill }));
+        }
+        if let Some(size) = self.size {
+            extra.push_str(&format!(",width={:.2},height={:.2},fontsize={:.1}",
+                                    0.75 + 1.25 * size,
+                                    0.5 + 0.75 * size,
+                                    14.0 + 16.0 * size));
+        }
+        // A denied license is always flagged with a red border, even on a highlighted or dimmed
+        // node, which otherwise get a blue or gray one
+        extra.push_str(if denied {
+            ",color=red,penwidth=3"
+        } else if self.highlighted {
+            ",color=blue,penwidth=2"
+        } else if self.dimmed {
+            ",color=gray80"
+        } else if muted {
+            ",color=gray60"
+        } else {
+            ""
+        });
+        if self.highlighted {
+            extra.push_str(",fontcolor=blue");
+        } else if self.dimmed || muted {
+            extra.push_str(",fontcolor=gray60");
         }
+        if !styles.is_empty() {
+            extra.push_str(&format!(",style={:?}", styles.join(",")));
+        }
+        writeln!(w,
+                 "[label={:?}{}{}{}{}];",
+                 name,
+                 self.style(c),
+                 merged,
+                 extra,
+                 links)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use config::{ColorBy, Config, ConfigBuilder, Links, Metric};
+    use super::ResolvedDep;
+
+    fn label(dep: &ResolvedDep, cfg: &Config) -> String {
+        let mut out = vec![];
+        dep.label(&mut out, cfg).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn dimmed_nodes_lose_their_fill() {
+        let cfg = ConfigBuilder::new().color_by(ColorBy::Metric(Metric::FanIn)).build();
+        let mut dep = ResolvedDep::new("a".to_owned(), "1.0.0".to_owned());
+        dep.heat = Some(1.0);
+        assert!(label(&dep, &cfg).contains(",fillcolor=\"0.000 1.000 1.000\""));
+        dep.dimmed = true;
+        let label = label(&dep, &cfg);
+        assert!(label.contains(",fillcolor=gray95,color=gray80,fontcolor=gray60"), "{}", label);
+    }
+
+    #[test]
+    fn denied_licenses_keep_their_red_border() {
+        let cfg = ConfigBuilder::new()
+                      .color_by(ColorBy::License)
+                      .deny_license("GPL-3.0")
+                      .build();
+        let mut dep = ResolvedDep::new("a".to_owned(), "1.0.0".to_owned());
+        dep.license = Some("GPL-3.0".to_owned());
+        dep.highlighted = true;
+        let label = label(&dep, &cfg);
+        assert!(label.contains(",color=red,penwidth=3,fontcolor=blue"), "{}", label);
+        assert!(!label.contains(",color=blue"), "{}", label);
+    }
+
+    #[test]
+    fn links_to_the_crate_source() {
+        let mut dep = ResolvedDep::new("serde".to_owned(), "1.0.100".to_owned());
+        assert_eq!(dep.url(Links::DocsRs), None);
+        dep.source = Some("registry+https://github.com/rust-lang/crates.io-index".to_owned());
+        assert_eq!(dep.url(Links::DocsRs), Some("https://docs.rs/serde/1.0.100".to_owned()));
+        assert_eq!(dep.url(Links::CratesIo),
+                   Some("https://crates.io/crates/serde/1.0.100".to_owned()));
+        dep.source = Some("git+https://github.com/serde-rs/serde?rev=abc#abcdef".to_owned());
+        assert_eq!(dep.url(Links::DocsRs), Some("https://github.com/serde-rs/serde".to_owned()));
+        dep.source = Some("path+file:///src/serde".to_owned());
+        assert_eq!(dep.url(Links::DocsRs), Some("file:///src/serde".to_owned()));
+    }
+
+    #[test]
+    fn tooltips_call_normal_deps_normal() {
+        let cfg = ConfigBuilder::new().build();
+        let mut dep = ResolvedDep::new("a".to_owned(), "1.0.0".to_owned());
+        dep.is_build = true;
+        assert_eq!(dep.tooltip(&cfg), "a v1.0.0\nsource: local path\nkind: normal");
+    }
+
+    #[test]
+    fn tooltips_list_licenses_only_when_they_were_looked_up() {
+        let mut dep = ResolvedDep::new("a".to_owned(), "1.0.0".to_owned());
+        dep.is_dev = true;
+        dep.license = Some("MIT".to_owned());
+        assert!(!dep.tooltip(&ConfigBuilder::new().build()).contains("license"));
+        let cfg = ConfigBuilder::new().color_by(ColorBy::License).build();
+        assert_eq!(dep.tooltip(&cfg),
+                   "a v1.0.0\nsource: local path\nkind: dev\nlicense: MIT");
     }
 }
@@ -0,0 +1,193 @@
+use std::collections::HashSet;
+use std::io::Write;
+
+use config::{DotLineShape, DotShape};
+use error::CliResult;
+use graph::DepGraph;
+
+impl<'c> DepGraph<'c> {
+    /// Renders the graph as a PlantUML diagram. Shapes without a PlantUML equivalent (diamond,
+    /// triangle) fall back to the default shape.
+    pub fn render_plantuml_to<W: Write>(mut self, output: &mut W) -> CliResult<()> {
+        debugln!("exec=render_plantuml_to;");
+        self.prepare();
+        try!(writeln!(output, "@startuml"));
+        if let Some(ref title) = self.cfg.title {
+            try!(writeln!(output, "title {}", title));
+        }
+        let aliases = plantuml_aliases(&(0..self.nodes.len())
+                                            .map(|i| self.node_id(i))
+                                            .collect::<Vec<_>>());
+        for (i, dep) in self.nodes.iter().enumerate() {
+            let style = dep.style(self.cfg);
+            let shape = match style.0 {
+                DotShape::Box => "rectangle",
+                _ => "usecase",
+            };
+            try!(write!(output,
+                        "{} \"{}\" as {}",
+                        shape,
+                        dep.display_name(self.cfg),
+                        aliases[i]));
+            if let Some(color) = style.1.name() {
+                try!(write!(output, " #line:{}", color));
+            }
+            try!(writeln!(output, ""));
+        }
+        for ed in &self.edges {
+            let mut attrs = vec![];
+            let style = ed.line_style(&self);
+            if let Some(color) = style.1.name() {
+                attrs.push(format!("#{}", color));
+            }
+            match style.0 {
+                DotLineShape::Dotted => attrs.push("dotted".to_owned()),
+                DotLineShape::Dashed => attrs.push("dashed".to_owned()),
+                DotLineShape::Solid => (),
+            }
+            let arrow = if attrs.is_empty() {
+                "-->".to_owned()
+            } else {
+                format!("-[{}]->", attrs.join(","))
+            };
+            try!(write!(output,
+                        "{} {} {}",
+                        aliases[ed.0],
+                        arrow,
+                        aliases[ed.1]));
+            let text = ed.text(&self);
+            if !text.is_empty() {
+                try!(write!(output, " : {}", text));
+            }
+            try!(writeln!(output, ""));
+        }
+        try!(writeln!(output, "@enduml"));
+        Ok(())
+    }
+
+    /// Renders the graph as a D2 diagram. Shapes without a D2 equivalent (triangle) fall back
+    /// to the default shape.
+    pub fn render_d2_to<W: Write>(mut self, output: &mut W) -> CliResult<()> {
+        debugln!("exec=render_d2_to;");
+        self.prepare();
+        if let Some(ref title) = self.cfg.title {
+            try!(writeln!(output, "title: {:?} {{", title));
+            try!(writeln!(output, "  shape: text"));
+            try!(writeln!(output, "  near: top-center"));
+            try!(writeln!(output, "}}"));
+        }
+        for (i, dep) in self.nodes.iter().enumerate() {
+            let style = dep.style(self.cfg);
+            try!(writeln!(output, "{:?}: {:?} {{", self.node_id(i), dep.display_name(self.cfg)));
+            match style.0 {
+                DotShape::Box => try!(writeln!(output, "  shape: rectangle")),
+                DotShape::Round => try!(writeln!(output, "  shape: oval")),
+                DotShape::Diamond => try!(writeln!(output, "  shape: diamond")),
+                DotShape::Triangle => (),
+            }
+            if let Some(color) = style.1.name() {
+                try!(writeln!(output, "  style.stroke: {}", color));
+            }
+            try!(writeln!(output, "}}"));
+        }
+        for ed in &self.edges {
+            let text = ed.text(&self);
+            if text.is_empty() {
+                try!(writeln!(output, "{:?} -> {:?} {{", self.node_id(ed.0), self.node_id(ed.1)));
+            } else {
+                try!(writeln!(output,
+                              "{:?} -> {:?}: {:?} {{",
+                              self.node_id(ed.0),
+                              self.node_id(ed.1),
+                              text));
+            }
+            let style = ed.line_style(&self);
+            if let Some(color) = style.1.name() {
+                try!(writeln!(output, "  style.stroke: {}", color));
+            }
+            match style.0 {
+                DotLineShape::Dotted => try!(writeln!(output, "  style.stroke-dash: 2")),
+                DotLineShape::Dashed => try!(writeln!(output, "  style.stroke-dash: 5")),
+                DotLineShape::Solid => (),
+            }
+            try!(writeln!(output, "}}"));
+        }
+        Ok(())
+    }
+}
+
+/// PlantUML aliases must be plain identifiers, so anything else in an ID becomes `_`. IDs that
+/// only differ there (`foo-bar@1.0` and `foo_bar@1.0`) get a numeric suffix to stay apart.
+fn plantuml_aliases(ids: &[String]) -> Vec<String> {
+    let mut used = HashSet::new();
+    ids.iter()
+       .map(|id| {
+           let alias = id.chars()
+                         .map(|c| if c.is_alphanumeric() { c } else { '_' })
+                         .collect::<String>();
+           let mut unique = alias.clone();
+           let mut n = 1;
+           while !used.insert(unique.clone()) {
+               n += 1;
+               unique = format!("{}_{}", alias, n);
+           }
+           unique
+       })
+       .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use config::{ConfigBuilder, DepStyle, DotColor, DotLineShape, DotShape, LineStyle};
+    use dep::DepKind;
+    use graph::tests::graph;
+    use super::plantuml_aliases;
+
+    #[test]
+    fn plantuml_aliases_are_unique() {
+        let ids = ["foo-bar@1.0".to_owned(), "foo_bar@1.0".to_owned(), "foo_bar_1_0".to_owned()];
+        assert_eq!(plantuml_aliases(&ids), ["foo_bar_1_0", "foo_bar_1_0_2", "foo_bar_1_0_3"]);
+    }
+
+    fn render(d2: bool) -> String {
+        let cfg = ConfigBuilder::new()
+                      .build_style(DepStyle(DotShape::Box, DotColor::Green))
+                      .dev_style(DepStyle(DotShape::Diamond, DotColor::Black))
+                      .dev_lines(LineStyle(DotLineShape::Dashed, DotColor::Red))
+                      .build();
+        let mut dg = graph(&cfg, &[("app", "a"), ("app", "b")]);
+        dg.nodes[2].is_build = false;
+        dg.nodes[2].is_dev = true;
+        dg.edges[1].2 = DepKind::Dev;
+        let mut out = vec![];
+        if d2 {
+            dg.render_d2_to(&mut out).unwrap();
+        } else {
+            dg.render_plantuml_to(&mut out).unwrap();
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn plantuml_maps_the_styles() {
+        assert_eq!(render(false),
+                   "@startuml\n\
+                    rectangle \"app\" as app_1_0_0 #line:green\n\
+                    rectangle \"a\" as a_1_0_0 #line:green\n\
+                    usecase \"b\" as b_1_0_0\n\
+                    app_1_0_0 --> a_1_0_0\n\
+                    app_1_0_0 -[#red,dashed]-> b_1_0_0\n\
+                    @enduml\n");
+    }
+
+    #[test]
+    fn d2_maps_the_styles() {
+        assert_eq!(render(true),
+                   "\"app@1.0.0\": \"app\" {\n  shape: rectangle\n  style.stroke: green\n}\n\
+                    \"a@1.0.0\": \"a\" {\n  shape: rectangle\n  style.stroke: green\n}\n\
+                    \"b@1.0.0\": \"b\" {\n  shape: diamond\n}\n\
+                    \"app@1.0.0\" -> \"a@1.0.0\" {\n}\n\
+                    \"app@1.0.0\" -> \"b@1.0.0\" {\n  style.stroke: red\n\
+                    \x20 style.stroke-dash: 5\n}\n");
+    }
+}
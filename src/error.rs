@@ -14,6 +14,9 @@ pub enum CliErrorKind {
     TomlTableRoot,
     TomlNoName,
     CurrentDir,
+    DotNotFound,
+    DotFailed(String),
+    Violations(String),
     Unknown,
     Io(io::Error),
     Generic(String),
@@ -26,6 +29,9 @@ impl CliErrorKind {
             CliErrorKind::TomlTableRoot => "No root table found for toml file",
             CliErrorKind::TomlNoName => "No name for package in toml file",
             CliErrorKind::CurrentDir => "Unable to determine the current working directory",
+            CliErrorKind::DotNotFound => "Unable to run GraphViz `dot`, make sure it's installed and in your PATH",
+            CliErrorKind::DotFailed(ref e) => e,
+            CliErrorKind::Violations(ref e) => e,
             CliErrorKind::UnknownBoolArg => "The value supplied isn't valid, either use 'true/false', 'yes/no', or the first letter of either.",
             CliErrorKind::Unknown => "An unknown fatal error has occurred, please consider filing a bug-report!",
             CliErrorKind::Io(ref e) => e.description(),
@@ -3,13 +3,14 @@ use std::fmt;
 #[cfg(all(feature = "color", not(target_os = "windows")))]
 use ansi_term::Colour::{Green, Red, Yellow};
 #[cfg(all(feature = "color", not(target_os = "windows")))]
-use ansi_term::ANSIString;
+use ansi_term::{ANSIString, Style};
 
 #[allow(dead_code)]
 pub enum Format<T> {
     Error(T),
     Warning(T),
     Good(T),
+    Dimmed(T),
 }
 
 #[cfg(all(feature = "color", not(target_os = "windows")))]
@@ -19,6 +20,7 @@ impl<T: AsRef<str>> Format<T> {
             Format::Error(ref e) => Red.bold().paint(e.as_ref()),
             Format::Warning(ref e) => Yellow.paint(e.as_ref()),
             Format::Good(ref e) => Green.paint(e.as_ref()),
+            Format::Dimmed(ref e) => Style::new().dimmed().paint(e.as_ref()),
         }
     }
 
@@ -38,6 +40,7 @@ impl<T: fmt::Display> Format<T> {
             Format::Error(ref e) => e,
             Format::Warning(ref e) => e,
             Format::Good(ref e) => e,
+            Format::Dimmed(ref e) => e,
         }
     }
 }
@@ -1,67 +1,178 @@
-use std::fmt;
+use std::cmp::Ordering;
+use std::collections::{HashMap, VecDeque};
 use std::io::{self, Write};
 
-use config::Config;
-use dep::ResolvedDep;
+use config::{Config, EdgeLabel, External, LineStyle};
+use dep::{DepKind, ResolvedDep};
 use error::CliResult;
+use util;
 
 pub type Nd = usize;
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone)]
-pub struct Ed(pub Nd, pub Nd);
+/// An edge from a parent to a child, with the kind the parent declares the dependency with and,
+/// when it's known, the requirement it's declared with.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+pub struct Ed(pub Nd, pub Nd, pub DepKind, pub Option<String>);
 
 impl Ed {
-    pub fn label<W: Write>(&self, w: &mut W, dg: &DepGraph) -> io::Result<()> {
-        use dep::DepKind::{Optional, Dev, Build};
+    /// Returns the kind of dependency this edge represents, which decides its line style.
+    ///
+    /// This is the kind the parent declares the dependency with, unless the parent itself is
+    /// only a dev or optional dependency. When the parent's manifest wasn't available, the kind
+    /// of the child is used instead.
+    pub fn kind(&self, dg: &DepGraph) -> DepKind {
+        use dep::DepKind::{Build, Unk};
         let parent = dg.get(self.0).unwrap().kind();
-        let child = dg.get(self.1).unwrap().kind();
+        let declared = if self.2 == Unk {
+            dg.get(self.1).unwrap().kind()
+        } else {
+            self.2
+        };
 
-        match (parent, child) {
-            (Build, Build) => writeln!(w, "[label=\"\"{}];", dg.cfg.build_lines),
-            (Build, Dev) => writeln!(w, "[label=\"\"{}];", dg.cfg.dev_lines),
-            (Build, Optional) => writeln!(w, "[label=\"\"{}];", dg.cfg.optional_lines),
-            (Optional, Build) => writeln!(w, "[label=\"\"{}];", dg.cfg.optional_lines),
-            (Optional, Dev) => writeln!(w, "[label=\"\"{}];", dg.cfg.optional_lines),
-            (Optional, Optional) => writeln!(w, "[label=\"\"{}];", dg.cfg.optional_lines),
-            (Dev, Build) => writeln!(w, "[label=\"\"{}];", dg.cfg.dev_lines),
-            (Dev, Dev) => writeln!(w, "[label=\"\"{}];", dg.cfg.dev_lines),
-            (Dev, Optional) => writeln!(w, "[label=\"\"{}];", dg.cfg.dev_lines),
-            _               => writeln!(w, "[label=\"\"];")
+        match (parent, declared) {
+            (Unk, _) | (_, Unk) => Unk,
+            (Build, declared) => declared,
+            (parent, _) => parent,
         }
     }
-}
 
-impl fmt::Display for Ed {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let &Ed(il, ir) = self;
-        write!(f, "N{} -> N{}", il, ir)
+    /// The line style for this edge.
+    pub fn line_style(&self, dg: &DepGraph) -> LineStyle {
+        match self.kind(dg) {
+            DepKind::Build => dg.cfg.build_lines,
+            DepKind::Dev => dg.cfg.dev_lines,
+            DepKind::Optional => dg.cfg.optional_lines,
+            DepKind::Unk => dg.cfg.unknown_lines,
+        }
+    }
+
+    /// The text shown on the edge, if any.
+    pub fn text(&self, dg: &DepGraph) -> &str {
+        match dg.cfg.edge_labels {
+            EdgeLabel::Req if self.0 == 0 => self.3.as_ref().map_or("", |r| &**r),
+            _ => "",
+        }
+    }
+
+    pub fn label<W: Write>(&self, w: &mut W, dg: &DepGraph) -> io::Result<()> {
+        let muted = if dg.cfg.external == External::Muted && !dg.nodes[self.1].is_local() {
+            ",color=gray60"
+        } else {
+            ""
+        };
+        // Edges breaking the layers or on a highlighted path stand out, the rest of a highlighted
+        // graph fades out, even between two highlighted crates
+        let (from, to) = (&dg.nodes[self.0], &dg.nodes[self.1]);
+        let emphasis = if dg.breaks_layers(self) {
+            ",color=red,penwidth=2"
+        } else if dg.is_highlighted(self) {
+            ",color=blue,penwidth=2"
+        } else if from.dimmed || to.dimmed || (from.highlighted && to.highlighted) {
+            ",color=gray80"
+        } else {
+            ""
+        };
+        writeln!(w,
+                 "[label={:?}{}{}{}];",
+                 self.text(dg),
+                 self.line_style(dg),
+                 muted,
+                 emphasis)
     }
 }
 
 #[derive(Debug)]
-pub struct DepGraph<'c, 'o>
-    where 'o: 'c
-{
+pub struct DepGraph<'c> {
     pub nodes: Vec<ResolvedDep>,
     pub edges: Vec<Ed>,
-    cfg: &'c Config<'o>,
+    /// The highlighted edges, as the name and version of both ends so they're still found once
+    /// the nodes are reordered or merged
+    pub highlighted_edges: Vec<((String, String), (String, String))>,
+    pub cfg: &'c Config,
 }
 
-impl<'c, 'o> DepGraph<'c, 'o> {
-    pub fn new(cfg: &'c Config<'o>) -> Self {
+impl<'c> DepGraph<'c> {
+    pub fn new(cfg: &'c Config) -> Self {
         DepGraph {
             nodes: vec![],
             edges: vec![],
+            highlighted_edges: vec![],
             cfg: cfg,
         }
     }
 
-    pub fn add_child(&mut self, parent: usize, dep_name: &str, dep_ver: &str) -> usize {
+    pub fn add_child(&mut self,
+                     parent: usize,
+                     dep_name: &str,
+                     dep_ver: &str,
+                     kind: DepKind)
+                     -> usize {
         let idr = self.find_or_add(dep_name, dep_ver);
-        self.edges.push(Ed(parent, idr));
+        self.edges.push(Ed(parent, idr, kind, None));
         idr
     }
 
+    /// Returns the shortest path of nodes from the root to `id`, including both ends.
+    pub fn path_from_root(&self, id: Nd) -> Option<Vec<Nd>> {
+        self.shortest_path(0, id, |_| false)
+    }
+
+    /// Returns the shortest path of nodes from `from` to `to`, including both ends, that doesn't
+    /// go through any of the nodes for which `skip` returns true.
+    pub fn shortest_path<F>(&self, from: Nd, to: Nd, skip: F) -> Option<Vec<Nd>>
+        where F: Fn(Nd) -> bool
+    {
+        let mut prev = vec![None; self.nodes.len()];
+        let mut queue = VecDeque::new();
+        queue.push_back(from);
+        prev[from] = Some(from);
+        while let Some(n) = queue.pop_front() {
+            if n == to {
+                let mut path = vec![to];
+                let mut cur = to;
+                while cur != from {
+                    cur = prev[cur].unwrap();
+                    path.push(cur);
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for &Ed(_, idr, _, _) in self.edges.iter().filter(|e| e.0 == n) {
+                if prev[idr].is_none() && !skip(idr) {
+                    prev[idr] = Some(n);
+                    queue.push_back(idr);
+                }
+            }
+        }
+        None
+    }
+
+    /// Returns the children of a node, sorted by name and version.
+    pub fn children(&self, id: Nd) -> Vec<Nd> {
+        let mut children = self.edges
+                               .iter()
+                               .filter(|e| e.0 == id)
+                               .map(|e| e.1)
+                               .collect::<Vec<_>>();
+        children.sort_by(|&l, &r| self.cmp_nodes(l, r));
+        children.dedup();
+        children
+    }
+
+    /// Orders two nodes by name, then by version.
+    fn cmp_nodes(&self, l: Nd, r: Nd) -> Ordering {
+        match self.nodes[l].name.cmp(&self.nodes[r].name) {
+            Ordering::Equal => util::cmp_versions(&self.nodes[l].ver, &self.nodes[r].ver),
+            ord => ord,
+        }
+    }
+
+    /// Returns whether another node has the same name (but a different version) as this one.
+    pub fn is_duplicate(&self, id: Nd) -> bool {
+        let name = &self.nodes[id].name;
+        self.nodes.iter().enumerate().any(|(i, d)| i != id && d.name == *name)
+    }
+
     pub fn get(&self, id: usize) -> Option<&ResolvedDep> {
         if id < self.nodes.len() {
             return Some(&self.nodes[id]);
@@ -80,35 +191,27 @@ impl<'c, 'o> DepGraph<'c, 'o> {
         self.shift_edges_after_node(id);
     }
 
+    /// Adjusts the edges to match the node indexes after the node `id` was removed.
     fn shift_edges_after_node(&mut self, id: usize) {
-        enum Side {
-            Left,
-            Right,
-        }
-        let mut to_upd = vec![];
-        for c in id..self.nodes.len() {
-            for (eid, &Ed(idl, idr)) in self.edges.iter().enumerate() {
-                if idl == c { to_upd.push((eid, Side::Left, c-1)); }
-                if idr == c { to_upd.push((eid, Side::Right, c-1)); }
+        for edge in self.edges.iter_mut() {
+            if edge.0 > id {
+                edge.0 -= 1;
             }
-        }
-        for (eid, side, new) in to_upd {
-            match side {
-                Side::Left => self.edges[eid].0 = new,
-                Side::Right => self.edges[eid].1 = new,
+            if edge.1 > id {
+                edge.1 -= 1;
             }
         }
     }
 
     pub fn remove_orphans(&mut self) {
         let len = self.nodes.len();
-        self.edges.retain(|&Ed(idl,idr)| idl < len && idr < len);
+        self.edges.retain(|&Ed(idl, idr, _, _)| idl < len && idr < len);
         debugln!("remove_orphans; nodes={:?}", self.nodes);
         loop {
             let mut removed = false;
             let mut used = vec![false; self.nodes.len()];
             used[0] = true;
-            for &Ed(_, idr) in &self.edges {
+            for &Ed(_, idr, _, _) in &self.edges {
                 debugln!("remove_orphans; idr={}", idr);
                 used[idr] = true;
             }
@@ -120,16 +223,8 @@ impl<'c, 'o> DepGraph<'c, 'o> {
                     self.nodes.remove(id);
 
                     // Remove edges originating from the removed node
-                    self.edges.retain(|&Ed(origin,_)| origin != id);
-                    // Adjust edges to match the new node indexes
-                    for edge in self.edges.iter_mut() {
-                        if edge.0 > id {
-                            edge.0 -= 1;
-                        }
-                        if edge.1 > id {
-                            edge.1 -= 1;
-                        }
-                    }
+                    self.edges.retain(|&Ed(origin, _, _, _)| origin != id);
+                    self.shift_edges_after_node(id);
                     removed = true;
                     break;
                 }
@@ -144,7 +239,7 @@ impl<'c, 'o> DepGraph<'c, 'o> {
         loop {
             let mut found = false;
             let mut self_p = vec![false; self.edges.len()];
-            for (eid ,&Ed(idl, idr)) in self.edges.iter().enumerate() {
+            for (eid ,&Ed(idl, idr, _, _)) in self.edges.iter().enumerate() {
                 if idl == idr {
                     found = true;
                     self_p[eid] = true;
@@ -212,20 +307,291 @@ impl<'c, 'o> DepGraph<'c, 'o> {
         self.nodes.len() - 1
     }
 
-    pub fn render_to<W: Write>(mut self, output: &mut W) -> CliResult<()> {
-        debugln!("exec=render_to;");
-        self.edges.sort();
-        self.edges.dedup();
+    /// Writes the graph-wide, node, and edge attributes from the config.
+    fn write_defaults<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        // Keys and values are written as quoted DOT IDs, which only escape `"`, so escapes such as
+        // `\l` reach graphviz untouched
+        fn quote(s: &str) -> String {
+            format!("\"{}\"", s.replace('"', "\\\""))
+        }
+
+        fn write_attrs<W: Write>(w: &mut W, stmt: &str, attrs: &[(String, String)]) -> io::Result<()> {
+            if attrs.is_empty() {
+                return Ok(());
+            }
+            try!(write!(w, "\t{} [", stmt));
+            for (i, &(ref key, ref value)) in attrs.iter().enumerate() {
+                if i > 0 {
+                    try!(write!(w, ","));
+                }
+                try!(write!(w, "{}={}", quote(key), quote(value)));
+            }
+            writeln!(w, "];")
+        }
+
+        let mut graph_attrs = vec![];
+        if let Some(dir) = self.cfg.rankdir {
+            graph_attrs.push(("rankdir".to_owned(), dir.to_string()));
+        }
+        if let Some(ref title) = self.cfg.title {
+            graph_attrs.push(("label".to_owned(), title.clone()));
+            graph_attrs.push(("labelloc".to_owned(), "t".to_owned()));
+        }
+        graph_attrs.extend(self.cfg.graph_attrs.iter().cloned());
+
+        try!(write_attrs(w, "graph", &graph_attrs));
+        try!(write_attrs(w, "node", &self.cfg.node_attrs));
+        write_attrs(w, "edge", &self.cfg.edge_attrs)
+    }
+
+    /// Sorts the edges and removes duplicates. When a crate depends on another one in more than
+    /// one way, the edge with the kind of the highest priority is kept.
+    fn dedup_edges(&mut self) {
+        // Sorting on the priority of `r` before `l` puts the highest priority first
+        self.edges.sort_by(|l, r| (l.0, l.1, r.2.priority()).cmp(&(r.0, r.1, l.2.priority())));
+        let mut last = None;
+        self.edges.retain(|&Ed(idl, idr, _, _)| {
+            let keep = last != Some((idl, idr));
+            last = Some((idl, idr));
+            keep
+        });
+    }
+
+    /// Removes duplicate, orphaned, and self-pointing edges, and orders the nodes. Reports and
+    /// checks run on this graph, since it still has every crate and edge.
+    pub fn normalize(&mut self) {
+        self.dedup_edges();
         self.remove_orphans();
         self.remove_self_pointing();
+        self.sort_nodes();
+    }
+
+    /// Normalizes the graph, then applies the options that only change how it's drawn (ex.
+    /// `--reduce`) before rendering.
+    pub fn prepare(&mut self) {
+        self.normalize();
+        if self.cfg.external == External::Shallow {
+            self.remove_transitive_external();
+        }
+        if self.cfg.merge_versions {
+            self.merge_versions();
+            self.remove_self_pointing();
+        }
+        if self.cfg.reduce {
+            self.reduce();
+        }
+        // Highlight what's left to draw, so a crate that was dropped doesn't count as a match
+        if !self.cfg.highlight.is_empty() && !self.highlight_matches() {
+            wlnerr!("warning: no crate matches --highlight {}", self.cfg.highlight.join(", "));
+        }
+        self.apply_metrics();
         debugln!("dg={:#?}", self);
+    }
+
+    /// Highlights the given nodes and dims the rest.
+    pub fn highlight<I: IntoIterator<Item = Nd>>(&mut self, ids: I) {
+        for id in ids {
+            self.nodes[id].highlighted = true;
+        }
+        for dep in self.nodes.iter_mut() {
+            dep.dimmed = !dep.highlighted;
+        }
+    }
+
+    /// Highlights the given edges. Their nodes should be highlighted with `highlight`.
+    pub fn highlight_edges<I: IntoIterator<Item = (Nd, Nd)>>(&mut self, edges: I) {
+        for (idl, idr) in edges {
+            let from = (self.nodes[idl].name.clone(), self.nodes[idl].ver.clone());
+            let to = (self.nodes[idr].name.clone(), self.nodes[idr].ver.clone());
+            self.highlighted_edges.push((from, to));
+        }
+    }
+
+    /// Returns whether an edge was highlighted, or was merged with one that was.
+    pub fn is_highlighted(&self, ed: &Ed) -> bool {
+        let is = |id: Nd, &(ref name, ref ver): &(String, String)| {
+            let dep = &self.nodes[id];
+            dep.name == *name && (dep.ver == *ver || dep.merged_vers.contains(ver))
+        };
+        self.highlighted_edges.iter().any(|&(ref from, ref to)| is(ed.0, from) && is(ed.1, to))
+    }
+
+    /// Highlights the crates matching one of the `--highlight` patterns, along with every crate
+    /// on a path from the root to them. Returns whether any crate matched.
+    pub fn highlight_matches(&mut self) -> bool {
+        let mut parents = vec![vec![]; self.nodes.len()];
+        for &Ed(idl, idr, _, _) in &self.edges {
+            parents[idr].push(idl);
+        }
+        let mut stack = (0..self.nodes.len())
+                            .filter(|&id| {
+                                self.cfg
+                                    .highlight
+                                    .iter()
+                                    .any(|p| util::matches_pattern(p, &self.nodes[id].name))
+                            })
+                            .collect::<Vec<_>>();
+        if stack.is_empty() {
+            return false;
+        }
+        let mut on_path = vec![false; self.nodes.len()];
+        while let Some(n) = stack.pop() {
+            if !on_path[n] {
+                on_path[n] = true;
+                stack.extend(parents[n].iter().cloned());
+            }
+        }
+        // Every edge going to a crate on a path is itself on a path from the root
+        let edges = self.edges
+                        .iter()
+                        .filter(|ed| on_path[ed.1])
+                        .map(|ed| (ed.0, ed.1))
+                        .collect::<Vec<_>>();
+        self.highlight((0..on_path.len()).filter(|&id| on_path[id]));
+        self.highlight_edges(edges);
+        true
+    }
+
+    /// Keeps only the external crates that a first-party crate depends on directly, by dropping
+    /// the dependencies of external crates.
+    fn remove_transitive_external(&mut self) {
+        let nodes = &self.nodes;
+        self.edges.retain(|&Ed(idl, _, _, _)| nodes[idl].is_local());
+        self.remove_orphans();
+    }
+
+    /// Collapses every version of a crate into a single node, merging its edges.
+    fn merge_versions(&mut self) {
+        let mut first_ids = HashMap::new();
+        let mut new_ids = (0..self.nodes.len()).collect::<Vec<_>>();
+        for (id, dep) in self.nodes.iter().enumerate() {
+            new_ids[id] = *first_ids.entry(dep.name.clone()).or_insert(id);
+        }
+
+        for id in 0..self.nodes.len() {
+            let first = new_ids[id];
+            if first == id {
+                continue;
+            }
+            debugln!("merge_versions; merging={} into={}", id, first);
+            if !self.nodes[first].is_merged() {
+                let ver = self.nodes[first].ver.clone();
+                self.nodes[first].merged_vers.push(ver);
+            }
+            let ver = self.nodes[id].ver.clone();
+            let (is_build, is_dev, is_optional) =
+                (self.nodes[id].is_build, self.nodes[id].is_dev, self.nodes[id].is_optional);
+            let (highlighted, dimmed) = (self.nodes[id].highlighted, self.nodes[id].dimmed);
+            let merged = &mut self.nodes[first];
+            merged.merged_vers.push(ver);
+            merged.merged_vers.sort_by(|l, r| util::cmp_versions(l, r));
+            merged.is_build |= is_build;
+            merged.is_dev |= is_dev;
+            merged.is_optional |= is_optional;
+            merged.highlighted |= highlighted;
+            merged.dimmed &= dimmed;
+        }
+
+        for edge in self.edges.iter_mut() {
+            edge.0 = new_ids[edge.0];
+            edge.1 = new_ids[edge.1];
+        }
+        self.dedup_edges();
+        // The merged-away nodes no longer have any edges
+        self.remove_orphans();
+    }
+
+    /// Removes every edge `A -> C` where `C` can also be reached from `A` through a longer
+    /// path. Edges are removed one at a time, so reachability is kept even with cycles.
+    fn reduce(&mut self) {
+        let mut adj = vec![vec![]; self.nodes.len()];
+        for (eid, &Ed(idl, _, _, _)) in self.edges.iter().enumerate() {
+            adj[idl].push(eid);
+        }
+        let mut removed = vec![false; self.edges.len()];
+
+        for eid in 0..self.edges.len() {
+            let (from, to) = (self.edges[eid].0, self.edges[eid].1);
+            // Look for `to` from `from` without using this edge
+            let mut seen = vec![false; self.nodes.len()];
+            let mut stack = vec![from];
+            seen[from] = true;
+            'search: while let Some(n) = stack.pop() {
+                for &other in &adj[n] {
+                    if other == eid || removed[other] {
+                        continue;
+                    }
+                    let next = self.edges[other].1;
+                    if next == to {
+                        debugln!("reduce; removing={:?}", self.edges[eid]);
+                        removed[eid] = true;
+                        break 'search;
+                    }
+                    if !seen[next] {
+                        seen[next] = true;
+                        stack.push(next);
+                    }
+                }
+            }
+        }
+
+        let mut removed = removed.into_iter();
+        self.edges.retain(|_| !removed.next().unwrap());
+    }
+
+    /// Orders the nodes by name and version (keeping the root first) so the output doesn't
+    /// depend on the order of the lock file.
+    fn sort_nodes(&mut self) {
+        let mut order = (1..self.nodes.len()).collect::<Vec<_>>();
+        order.sort_by(|&l, &r| self.cmp_nodes(l, r));
+        order.insert(0, 0);
+
+        let mut new_ids = vec![0; self.nodes.len()];
+        for (new, &old) in order.iter().enumerate() {
+            new_ids[old] = new;
+        }
+        let mut nodes = self.nodes.drain(..).map(Some).collect::<Vec<_>>();
+        self.nodes = order.iter().map(|&old| nodes[old].take().unwrap()).collect();
+        for edge in self.edges.iter_mut() {
+            edge.0 = new_ids[edge.0];
+            edge.1 = new_ids[edge.1];
+        }
+        self.edges.sort();
+    }
+
+    /// The ID a node is written with, derived from the crate itself (ex. `serde@1.0.100`) so it
+    /// stays the same when unrelated parts of the graph change.
+    pub fn node_id(&self, id: Nd) -> String {
+        if self.nodes[id].is_merged() {
+            return self.nodes[id].name.clone();
+        }
+        format!("{}@{}", self.nodes[id].name, self.nodes[id].ver)
+    }
+
+    pub fn render_to<W: Write>(mut self, output: &mut W) -> CliResult<()> {
+        debugln!("exec=render_to;");
+        self.prepare();
         try!(writeln!(output, "{}", "digraph dependencies {"));
+        try!(self.write_defaults(output));
+        if self.cfg.group_internal {
+            try!(writeln!(output, "\tsubgraph cluster_internal {{"));
+            try!(writeln!(output, "\t\tlabel={:?};", self.nodes[0].name));
+            for (i, dep) in self.nodes.iter().enumerate().filter(|&(_, d)| d.is_local()) {
+                try!(write!(output, "\t\t{:?}", self.node_id(i)));
+                try!(dep.label(output, self.cfg));
+            }
+            try!(writeln!(output, "\t}}"));
+        }
         for (i, dep) in self.nodes.iter().enumerate() {
-            try!(write!(output, "\tN{}", i));
+            if self.cfg.group_internal && dep.is_local() {
+                continue;
+            }
+            try!(write!(output, "\t{:?}", self.node_id(i)));
             try!(dep.label(output, self.cfg));
         }
+        try!(self.write_layer_ranks(output));
         for ed in &self.edges {
-            try!(write!(output, "\t{}", ed));
+            try!(write!(output, "\t{:?} -> {:?}", self.node_id(ed.0), self.node_id(ed.1)));
             try!(ed.label(output, &self));
         }
         try!(writeln!(output, "{}", "}"));
@@ -233,3 +599,253 @@ impl<'c, 'o> DepGraph<'c, 'o> {
     }
 
 }
+
+#[cfg(test)]
+pub mod tests {
+    use config::{Config, ConfigBuilder, DepStyle, DotColor, DotLineShape, DotShape, External,
+                 LineStyle};
+    use dep::DepKind;
+    use super::{DepGraph, Ed};
+
+    /// Builds a graph from `parent -> child` edges, where each crate is written as `name` (for
+    /// v1.0.0) or `name@version`. The first parent is the root, and every crate is a build dep.
+    pub fn graph<'c>(cfg: &'c Config, edges: &[(&str, &str)]) -> DepGraph<'c> {
+        fn split(spec: &str) -> (&str, &str) {
+            match spec.find('@') {
+                Some(i) => (&spec[..i], &spec[i + 1..]),
+                None => (spec, "1.0.0"),
+            }
+        }
+
+        let mut dg = DepGraph::new(cfg);
+        for &(parent, child) in edges {
+            let (name, ver) = split(parent);
+            let idl = dg.find_or_add(name, ver);
+            let (name, ver) = split(child);
+            dg.add_child(idl, name, ver, DepKind::Build);
+        }
+        for dep in &mut dg.nodes {
+            dep.is_build = true;
+        }
+        dg
+    }
+
+    /// Returns the edges of a graph as sorted `parent -> child` names.
+    pub fn edges(dg: &DepGraph) -> Vec<String> {
+        let mut edges = dg.edges
+                          .iter()
+                          .map(|ed| format!("{} -> {}", dg.nodes[ed.0].name, dg.nodes[ed.1].name))
+                          .collect::<Vec<_>>();
+        edges.sort();
+        edges
+    }
+
+    /// Returns the names of the nodes of a graph, in order.
+    pub fn names<'a>(dg: &'a DepGraph) -> Vec<&'a str> {
+        dg.nodes.iter().map(|d| &*d.name).collect()
+    }
+
+    #[test]
+    fn dedup_edges_keeps_the_kind_with_the_highest_priority() {
+        let cfg = ConfigBuilder::new().build();
+        let mut dg = graph(&cfg, &[("app", "a"), ("app", "b"), ("app", "c")]);
+        dg.edges.clear();
+        for &(child, kind) in &[(1, DepKind::Dev), (1, DepKind::Optional), (2, DepKind::Dev),
+                                (2, DepKind::Build), (2, DepKind::Optional), (3, DepKind::Unk),
+                                (3, DepKind::Dev)] {
+            dg.edges.push(Ed(0, child, kind, None));
+        }
+        dg.dedup_edges();
+        assert_eq!(dg.edges,
+                   [Ed(0, 1, DepKind::Optional, None),
+                    Ed(0, 2, DepKind::Build, None),
+                    Ed(0, 3, DepKind::Dev, None)]);
+    }
+
+    #[test]
+    fn defaults_are_written_as_quoted_dot_ids() {
+        let cfg = ConfigBuilder::new()
+                      .title("The \"app\" crate")
+                      .node_attr("label", "left\\l")
+                      .edge_attr("arrowhead", "none")
+                      .build();
+        let mut out = vec![];
+        graph(&cfg, &[("app", "a")]).write_defaults(&mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(),
+                   "\tgraph [\"label\"=\"The \\\"app\\\" crate\",\"labelloc\"=\"t\"];\n\
+                    \tnode [\"label\"=\"left\\l\"];\n\
+                    \tedge [\"arrowhead\"=\"none\"];\n");
+    }
+
+    #[test]
+    fn highlights_matches_and_their_paths_from_the_root() {
+        let cfg = ConfigBuilder::new().highlight("openssl*").build();
+        let mut dg = graph(&cfg,
+                           &[("app", "native-tls"), ("native-tls", "openssl"),
+                             ("openssl", "openssl-sys"), ("openssl-sys", "libc"), ("app", "log"),
+                             ("log", "libc")]);
+        assert!(dg.highlight_matches());
+        let highlighted = dg.nodes
+                            .iter()
+                            .filter(|d| d.highlighted)
+                            .map(|d| &*d.name)
+                            .collect::<Vec<_>>();
+        assert_eq!(highlighted, ["app", "native-tls", "openssl", "openssl-sys"]);
+        assert!(dg.nodes.iter().all(|d| d.dimmed != d.highlighted));
+        let edges = dg.edges
+                      .iter()
+                      .filter(|ed| dg.is_highlighted(ed))
+                      .map(|ed| format!("{} -> {}", dg.nodes[ed.0].name, dg.nodes[ed.1].name))
+                      .collect::<Vec<_>>();
+        assert_eq!(edges,
+                   ["app -> native-tls", "native-tls -> openssl", "openssl -> openssl-sys"]);
+    }
+
+    #[test]
+    fn highlight_ignores_crates_that_are_not_drawn() {
+        let cfg = ConfigBuilder::new().highlight("orphan").build();
+        let mut dg = graph(&cfg, &[("app", "log"), ("orphan", "log")]);
+        dg.prepare();
+        assert_eq!(names(&dg), ["app", "log"]);
+        assert!(dg.nodes.iter().all(|d| !d.highlighted && !d.dimmed));
+    }
+
+    #[test]
+    fn merge_versions_lists_versions_in_semver_order() {
+        let cfg = ConfigBuilder::new().merge_versions(true).build();
+        let mut dg = graph(&cfg,
+                           &[("app", "a@0.9.0"), ("app", "b"), ("b", "a@0.10.0"),
+                             ("b", "a@0.10.0-beta"), ("a@0.9.0", "c"), ("a@0.10.0", "c")]);
+        dg.normalize();
+        dg.merge_versions();
+        assert_eq!(names(&dg), ["app", "a", "b", "c"]);
+        assert_eq!(dg.nodes[1].merged_vers, ["0.9.0", "0.10.0-beta", "0.10.0"]);
+        assert_eq!(dg.nodes[1].versions(), "0.9.0 | 0.10.0-beta | 0.10.0");
+        assert_eq!(edges(&dg), ["a -> c", "app -> a", "app -> b", "b -> a"]);
+    }
+
+    #[test]
+    fn merge_versions_combines_the_kinds() {
+        let cfg = ConfigBuilder::new().merge_versions(true).build();
+        let mut dg = graph(&cfg, &[("app", "a@1.0.0"), ("app", "b"), ("b", "a@2.0.0")]);
+        dg.nodes[1].is_build = false;
+        dg.nodes[1].is_dev = true;
+        dg.nodes[1].highlighted = true;
+        dg.merge_versions();
+        assert_eq!(names(&dg), ["app", "a", "b"]);
+        assert!(dg.nodes[1].is_build && dg.nodes[1].is_dev && dg.nodes[1].highlighted);
+        assert!(!dg.nodes[2].is_merged());
+    }
+
+    #[test]
+    fn reduce_removes_edges_implied_by_longer_paths() {
+        let cfg = ConfigBuilder::new().reduce(true).build();
+        let mut dg = graph(&cfg,
+                           &[("app", "a"), ("app", "b"), ("app", "c"), ("a", "b"), ("b", "c"),
+                             ("a", "d")]);
+        dg.reduce();
+        assert_eq!(edges(&dg), ["a -> b", "a -> d", "app -> a", "b -> c"]);
+    }
+
+    #[test]
+    fn reduce_keeps_crates_reachable_through_cycles() {
+        let cfg = ConfigBuilder::new().reduce(true).build();
+        let mut dg = graph(&cfg, &[("app", "a"), ("app", "b"), ("a", "b"), ("b", "a")]);
+        dg.reduce();
+        // Either `app -> a` or `app -> b` is implied by the cycle, but not both
+        assert_eq!(edges(&dg), ["a -> b", "app -> b", "b -> a"]);
+    }
+
+    #[test]
+    fn remove_keeps_edges_pointing_at_the_right_nodes() {
+        let cfg = ConfigBuilder::new().build();
+        let mut dg = graph(&cfg, &[("app", "a"), ("app", "b"), ("b", "c")]);
+        dg.remove(1);
+        assert_eq!(names(&dg), ["app", "b", "c"]);
+        assert_eq!(edges(&dg), ["app -> b", "b -> c"]);
+    }
+
+    #[test]
+    fn remove_orphans_drops_unreachable_crates() {
+        let cfg = ConfigBuilder::new().build();
+        let mut dg = graph(&cfg, &[("app", "a"), ("b", "c")]);
+        dg.remove_orphans();
+        assert_eq!(names(&dg), ["app", "a"]);
+        assert_eq!(edges(&dg), ["app -> a"]);
+    }
+
+    #[test]
+    fn set_root_moves_the_root_first() {
+        let cfg = ConfigBuilder::new().build();
+        let mut dg = graph(&cfg, &[("a", "app"), ("app", "b")]);
+        assert!(dg.set_root("app", "1.0.0"));
+        assert_eq!(names(&dg), ["app", "a", "b"]);
+        assert_eq!(edges(&dg), ["a -> app", "app -> b"]);
+        assert!(!dg.set_root("missing", "1.0.0"));
+    }
+
+    #[test]
+    fn shallow_keeps_only_direct_deps_of_first_party_crates() {
+        let cfg = ConfigBuilder::new().external(External::Shallow).build();
+        let mut dg = graph(&cfg,
+                           &[("app", "core"), ("core", "serde"), ("serde", "serde_derive"),
+                             ("app", "log"), ("log", "libc"), ("core", "libc")]);
+        for dep in &mut dg.nodes {
+            if dep.name != "app" && dep.name != "core" {
+                dep.source = Some("registry+https://github.com/rust-lang/crates.io-index"
+                                      .to_owned());
+            }
+        }
+        dg.remove_transitive_external();
+        assert_eq!(names(&dg), ["app", "core", "serde", "log", "libc"]);
+        assert_eq!(edges(&dg), ["app -> core", "app -> log", "core -> libc", "core -> serde"]);
+    }
+
+    #[test]
+    fn shortest_path_skips_nodes() {
+        let cfg = ConfigBuilder::new().build();
+        let dg = graph(&cfg, &[("app", "a"), ("a", "c"), ("app", "b"), ("b", "x"), ("x", "c")]);
+        let c = dg.find("c", "1.0.0").unwrap();
+        let a = dg.find("a", "1.0.0").unwrap();
+        let path = dg.path_from_root(c).unwrap();
+        assert_eq!(path.iter().map(|&n| &*dg.nodes[n].name).collect::<Vec<_>>(),
+                   ["app", "a", "c"]);
+        let path = dg.shortest_path(0, c, |n| n == a).unwrap();
+        assert_eq!(path.iter().map(|&n| &*dg.nodes[n].name).collect::<Vec<_>>(),
+                   ["app", "b", "x", "c"]);
+        assert_eq!(dg.shortest_path(c, 0, |_| false), None);
+    }
+
+    #[test]
+    fn sort_nodes_keeps_the_root_first() {
+        let cfg = ConfigBuilder::new().build();
+        let mut dg = graph(&cfg,
+                           &[("app", "c"), ("app", "b"), ("c", "a@10.0.0"), ("b", "a@9.0.0")]);
+        dg.sort_nodes();
+        assert_eq!(names(&dg), ["app", "a", "a", "b", "c"]);
+        assert_eq!(dg.nodes[1].ver, "9.0.0");
+        assert_eq!(edges(&dg), ["app -> b", "app -> c", "b -> a", "c -> a"]);
+    }
+
+    #[test]
+    fn unknown_kinds_use_their_own_style() {
+        let cfg = ConfigBuilder::new()
+                      .unknown_style(DepStyle(DotShape::Triangle, DotColor::Orange))
+                      .unknown_lines(LineStyle(DotLineShape::Dotted, DotColor::Purple))
+                      .build();
+        let mut dg = graph(&cfg, &[("app", "a"), ("app", "b")]);
+        dg.nodes[2].is_build = false;
+        dg.edges[1].2 = DepKind::Unk;
+        let mut out = vec![];
+        dg.render_to(&mut out).unwrap();
+        let dot = String::from_utf8(out).unwrap();
+        assert!(dot.contains("\t\"a@1.0.0\"[label=\"a\"];\n"), "{}", dot);
+        assert!(dot.contains("\t\"b@1.0.0\"[label=\"b\",shape=triangle,color=orange];\n"),
+                "{}",
+                dot);
+        assert!(dot.contains("\t\"app@1.0.0\" -> \"b@1.0.0\"[label=\"\",style=dotted,\
+                              color=purple];\n"),
+                "{}",
+                dot);
+    }
+}
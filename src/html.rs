@@ -0,0 +1,78 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use rustc_serialize::json::Json;
+
+use error::CliResult;
+use graph::DepGraph;
+use util;
+
+static VIEWER: &'static str = include_str!("viewer.html");
+
+impl<'c> DepGraph<'c> {
+    /// Renders the graph as a single, self-contained HTML page with an interactive viewer. The
+    /// nodes and edges are embedded as JSON so the page works without any network access.
+    pub fn render_html_to<W: Write>(mut self, output: &mut W) -> CliResult<()> {
+        debugln!("exec=render_html_to;");
+        self.prepare();
+
+        let nodes = self.nodes
+                        .iter()
+                        .map(|dep| {
+                            let mut obj = BTreeMap::new();
+                            obj.insert("name".to_owned(), Json::String(dep.name.clone()));
+                            obj.insert("version".to_owned(), Json::String(dep.versions()));
+                            obj.insert("kind".to_owned(), Json::String(dep.kind().to_string()));
+                            obj.insert("source".to_owned(),
+                                       Json::String(dep.source
+                                                       .clone()
+                                                       .unwrap_or_else(|| "local path".to_owned())));
+                            Json::Object(obj)
+                        })
+                        .collect();
+        let edges = self.edges
+                        .iter()
+                        .map(|ed| Json::Array(vec![Json::U64(ed.0 as u64), Json::U64(ed.1 as u64)]))
+                        .collect();
+        let mut data = BTreeMap::new();
+        data.insert("nodes".to_owned(), Json::Array(nodes));
+        data.insert("edges".to_owned(), Json::Array(edges));
+
+        // Keep a crate name from ever closing the <script> tag early
+        let data = Json::Object(data).to_string().replace("</", "<\\/");
+        let title = util::xml_escape(&format!("{} dependencies", self.nodes[0].name));
+
+        try!(write!(output,
+                    "{}",
+                    VIEWER.replace("{{TITLE}}", &title).replace("{{DATA}}", &data)));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use config::ConfigBuilder;
+    use graph::tests::graph;
+
+    #[test]
+    fn embeds_the_graph_as_json() {
+        let cfg = ConfigBuilder::new().build();
+        let mut dg = graph(&cfg, &[("app", "a"), ("app", "b</script>")]);
+        dg.nodes[2].is_build = false;
+        dg.nodes[2].is_dev = true;
+        let mut out = vec![];
+        dg.render_html_to(&mut out).unwrap();
+        let html = String::from_utf8(out).unwrap();
+        assert!(html.contains("<title>app dependencies</title>"), "{}", html);
+        assert!(html.contains("{\"edges\":[[0,1],[0,2]],\"nodes\":[\
+                               {\"kind\":\"normal\",\"name\":\"app\",\"source\":\"local path\",\
+                               \"version\":\"1.0.0\"},\
+                               {\"kind\":\"normal\",\"name\":\"a\",\"source\":\"local path\",\
+                               \"version\":\"1.0.0\"},\
+                               {\"kind\":\"dev\",\"name\":\"b<\\/script>\",\
+                               \"source\":\"local path\",\"version\":\"1.0.0\"}]}"),
+                "{}",
+                html);
+        assert!(!html.contains("{{"), "{}", html);
+    }
+}
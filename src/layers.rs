@@ -0,0 +1,175 @@
+use std::io::{self, Write};
+
+use toml::Value;
+
+use error::{CliError, CliErrorKind, CliResult};
+use graph::{DepGraph, Ed, Nd};
+use util;
+
+/// An architectural layer, holding the crates whose names match one of its patterns.
+#[derive(Debug, Clone)]
+pub struct Layer {
+    pub name: String,
+    pub patterns: Vec<String>,
+}
+
+fn invalid(what: &str) -> CliError {
+    From::from(CliErrorKind::Generic(format!("invalid layers: {}", what)))
+}
+
+/// Reads the layers from the `[layers]` table of a policy file, lowest layer first:
+///
+/// ```toml
+/// [layers]
+/// order = "core < domain < app"
+/// core = ["core", "*-core"]
+/// domain = ["*-domain"]
+/// ```
+///
+/// A layer without patterns holds the crate of the same name.
+pub fn from_toml(value: &Value) -> CliResult<Vec<Layer>> {
+    let table = try!(value.as_table().ok_or_else(|| invalid("'layers' must be a table")));
+    let order = try!(table.get("order")
+                          .and_then(Value::as_str)
+                          .ok_or_else(|| invalid("missing 'order', ex. \"core < domain < app\"")));
+
+    let mut layers = order.split('<')
+                          .map(str::trim)
+                          .filter(|name| !name.is_empty())
+                          .map(|name| {
+                              Layer {
+                                  name: name.to_owned(),
+                                  patterns: vec![name.to_owned()],
+                              }
+                          })
+                          .collect::<Vec<_>>();
+    for (key, patterns) in table.iter().filter(|&(k, _)| k != "order") {
+        let layer = try!(layers.iter_mut()
+                               .find(|l| l.name == *key)
+                               .ok_or_else(|| invalid(&format!("'{}' isn't in the order", key))));
+        let patterns = try!(patterns.as_slice()
+                                    .ok_or_else(|| {
+                                        invalid(&format!("'{}' must be a list of patterns", key))
+                                    }));
+        layer.patterns = patterns.iter().filter_map(Value::as_str).map(ToOwned::to_owned).collect();
+    }
+    Ok(layers)
+}
+
+impl<'c> DepGraph<'c> {
+    /// Returns the index of the lowest layer a node belongs to, if any.
+    pub fn layer_of(&self, id: Nd) -> Option<usize> {
+        let name = &self.nodes[id].name;
+        self.cfg
+            .policy
+            .layers
+            .iter()
+            .position(|l| l.patterns.iter().any(|p| util::matches_pattern(p, name)))
+    }
+
+    /// Returns whether an edge goes from a lower layer to a higher one.
+    pub fn breaks_layers(&self, ed: &Ed) -> bool {
+        match (self.layer_of(ed.0), self.layer_of(ed.1)) {
+            (Some(from), Some(to)) => from < to,
+            _ => false,
+        }
+    }
+
+    /// Writes a `rank=same` group for each layer, so each layer is drawn on its own row.
+    pub fn write_layer_ranks<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        for layer in 0..self.cfg.policy.layers.len() {
+            let ids = (0..self.nodes.len())
+                          .filter(|&id| self.layer_of(id) == Some(layer))
+                          .map(|id| format!("{:?};", self.node_id(id)))
+                          .collect::<Vec<_>>();
+            if !ids.is_empty() {
+                try!(writeln!(w, "\t{{rank=same; {}}}", ids.join(" ")));
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes every edge going from a lower layer to a higher one and returns how many were found.
+    pub fn write_layers_report<W: Write>(&self, w: &mut W) -> CliResult<usize> {
+        let layers = &self.cfg.policy.layers;
+        let mut found = 0;
+        for ed in self.edges.iter().filter(|ed| self.breaks_layers(ed)) {
+            found += 1;
+            let (from, to) = (&self.nodes[ed.0], &self.nodes[ed.1]);
+            try!(writeln!(w,
+                          "{} v{} ({}) depends on {} v{} from the higher layer {}",
+                          from.name,
+                          from.ver,
+                          layers[self.layer_of(ed.0).unwrap()].name,
+                          to.name,
+                          to.ver,
+                          layers[self.layer_of(ed.1).unwrap()].name));
+        }
+        Ok(found)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use toml::Parser;
+
+    use config::ConfigBuilder;
+    use graph::tests::graph;
+    use policy::Policy;
+    use super::from_toml;
+
+    fn policy(s: &str) -> Policy {
+        Policy::from_toml(&Parser::new(s).parse().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn reads_layers_in_order() {
+        let layers = policy("[layers]\norder = \"core < domain < app\"\ncore = [\"core\", \"*-core\"]")
+                         .layers;
+        assert_eq!(layers.iter().map(|l| &*l.name).collect::<Vec<_>>(),
+                   ["core", "domain", "app"]);
+        assert_eq!(layers[0].patterns, ["core", "*-core"]);
+        assert_eq!(layers[1].patterns, ["domain"]);
+    }
+
+    #[test]
+    fn rejects_invalid_layers() {
+        for s in &["[layers]\ncore = [\"core\"]", "[layers]\norder = \"a < b\"\nc = [\"c\"]",
+                   "[layers]\norder = \"a < b\"\na = \"a\""] {
+            let toml = Parser::new(s).parse().unwrap();
+            assert!(from_toml(&toml["layers"]).is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn finds_edges_to_higher_layers() {
+        let cfg = ConfigBuilder::new()
+                      .policy(policy("[layers]\norder = \"core < domain < app\"\n\
+                                      domain = [\"*-domain\"]"))
+                      .build();
+        let dg = graph(&cfg,
+                       &[("app", "user-domain"), ("user-domain", "core"), ("core", "app"),
+                         ("core", "serde")]);
+        assert_eq!(dg.layer_of(0), Some(2));
+        assert_eq!(dg.layer_of(1), Some(1));
+        assert_eq!(dg.layer_of(3), None);
+        let broken = dg.edges.iter().filter(|ed| dg.breaks_layers(ed)).collect::<Vec<_>>();
+        assert_eq!(broken.len(), 1);
+        assert_eq!((&*dg.nodes[broken[0].0].name, &*dg.nodes[broken[0].1].name),
+                   ("core", "app"));
+    }
+
+    #[test]
+    fn report_ignores_reduce() {
+        let cfg = ConfigBuilder::new()
+                      .reduce(true)
+                      .policy(policy("[layers]\norder = \"core < app\""))
+                      .build();
+        let mut dg = graph(&cfg, &[("web", "core"), ("core", "log"), ("log", "app"), ("core", "app")]);
+        dg.normalize();
+        let mut out = vec![];
+        assert_eq!(dg.write_layers_report(&mut out).unwrap(), 1);
+        assert_eq!(String::from_utf8(out).unwrap(),
+                   "core v1.0.0 (core) depends on app v1.0.0 from the higher layer app\n");
+    }
+}
@@ -0,0 +1,359 @@
+use std::ascii::AsciiExt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use toml::{Table, Value};
+
+use error::CliResult;
+use graph::DepGraph;
+use util;
+
+/// Colors used for licenses that weren't given a color on the command line.
+static PALETTE: [&'static str; 8] = ["lightblue", "palegreen", "khaki", "lightpink", "plum",
+                                     "lightsalmon", "paleturquoise", "wheat"];
+
+/// Looks up the license of a crate from its manifest, unpacked under one of `dirs`.
+pub fn find_license(dirs: &[PathBuf], name: &str, ver: &str) -> Option<String> {
+    util::find_crate_manifest(dirs, name, ver)
+        .and_then(|manifest| util::toml_from_file(&manifest).ok())
+        .and_then(|toml| license_from_manifest(&toml))
+}
+
+/// Returns the `license` of a package manifest, or `custom` when it only has a `license-file`.
+pub fn license_from_manifest(manifest: &Table) -> Option<String> {
+    let package = match manifest.get("package") {
+        Some(&Value::Table(ref t)) => t,
+        _ => return None,
+    };
+    match (package.get("license"), package.get("license-file")) {
+        (Some(&Value::String(ref l)), _) => Some(l.clone()),
+        (_, Some(_)) => Some("custom".to_owned()),
+        _ => None,
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    Word(String),
+}
+
+/// Splits a license expression into parentheses and words. The `/` of old-style expressions
+/// (ex. `MIT/Apache-2.0`) is read as `OR`.
+fn tokenize(expr: &str) -> Vec<Token> {
+    fn end_word(word: &mut String, tokens: &mut Vec<Token>) {
+        if !word.is_empty() {
+            tokens.push(Token::Word(word.clone()));
+            word.clear();
+        }
+    }
+
+    let mut tokens = vec![];
+    let mut word = String::new();
+    for c in expr.chars() {
+        match c {
+            '(' | ')' | '/' => {
+                end_word(&mut word, &mut tokens);
+                tokens.push(match c {
+                    '(' => Token::Open,
+                    ')' => Token::Close,
+                    _ => Token::Word("OR".to_owned()),
+                });
+            }
+            c if c.is_whitespace() => end_word(&mut word, &mut tokens),
+            c => word.push(c),
+        }
+    }
+    end_word(&mut word, &mut tokens);
+    tokens
+}
+
+/// Reduces a license ID to the license it names, so `GPL-3.0-only`, `GPL-3.0-or-later`, and
+/// `GPL-3.0+` all match `GPL-3.0`.
+fn normalize(id: &str) -> String {
+    let id = id.trim_right_matches('+');
+    let id = if id.ends_with("-only") {
+        &id[..id.len() - 5]
+    } else if id.ends_with("-or-later") {
+        &id[..id.len() - 9]
+    } else {
+        id
+    };
+    id.to_ascii_lowercase()
+}
+
+fn is_operator(word: &str) -> bool {
+    ["AND", "OR", "WITH"].iter().any(|op| op.eq_ignore_ascii_case(word))
+}
+
+/// A parsed SPDX license expression, with normalized license IDs.
+#[derive(Debug, PartialEq)]
+enum Expr {
+    License(String),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    /// An `OR` expression is only denied when both sides are, and an `AND` expression as soon
+    /// as either side is.
+    fn is_denied(&self, deny: &[String]) -> bool {
+        match *self {
+            Expr::License(ref id) => deny.contains(id),
+            Expr::And(ref l, ref r) => l.is_denied(deny) || r.is_denied(deny),
+            Expr::Or(ref l, ref r) => l.is_denied(deny) && r.is_denied(deny),
+        }
+    }
+}
+
+/// Parses license expressions, where `WITH` binds tighter than `AND`, which binds tighter than
+/// `OR`. Operators can be in any case. The exception of a `WITH` is dropped, since it only
+/// loosens the license.
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn parse(expr: &str) -> Result<Expr, ()> {
+        let tokens = tokenize(expr);
+        let mut p = Parser {
+            tokens: &tokens,
+            pos: 0,
+        };
+        let expr = try!(p.or());
+        if p.pos != tokens.len() {
+            return Err(());
+        }
+        Ok(expr)
+    }
+
+    fn eat(&mut self, op: &str) -> bool {
+        match self.tokens.get(self.pos) {
+            Some(&Token::Word(ref w)) if w.eq_ignore_ascii_case(op) => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, ()> {
+        let mut expr = try!(self.and());
+        while self.eat("OR") {
+            expr = Expr::Or(Box::new(expr), Box::new(try!(self.and())));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, ()> {
+        let mut expr = try!(self.with());
+        while self.eat("AND") {
+            expr = Expr::And(Box::new(expr), Box::new(try!(self.with())));
+        }
+        Ok(expr)
+    }
+
+    fn with(&mut self) -> Result<Expr, ()> {
+        let expr = try!(self.primary());
+        if self.eat("WITH") {
+            try!(self.license());
+        }
+        Ok(expr)
+    }
+
+    fn primary(&mut self) -> Result<Expr, ()> {
+        if self.tokens.get(self.pos) != Some(&Token::Open) {
+            return self.license().map(|id| Expr::License(normalize(&id)));
+        }
+        self.pos += 1;
+        let expr = try!(self.or());
+        if self.tokens.get(self.pos) != Some(&Token::Close) {
+            return Err(());
+        }
+        self.pos += 1;
+        Ok(expr)
+    }
+
+    fn license(&mut self) -> Result<String, ()> {
+        match self.tokens.get(self.pos) {
+            Some(&Token::Word(ref w)) if !is_operator(w) => {
+                self.pos += 1;
+                Ok(w.clone())
+            }
+            _ => Err(()),
+        }
+    }
+}
+
+/// Returns whether a license expression is denied, following the SPDX rules: an `OR`
+/// expression is only denied when every alternative is, and an `AND` expression as soon as any
+/// part is. An expression that can't be parsed is denied if it names any denied license.
+pub fn is_denied(expr: &str, deny: &[String]) -> bool {
+    if deny.is_empty() {
+        return false;
+    }
+    let deny = deny.iter().map(|d| normalize(d)).collect::<Vec<_>>();
+    match Parser::parse(expr) {
+        Ok(expr) => expr.is_denied(&deny),
+        Err(()) => {
+            tokenize(expr).iter().any(|t| {
+                match *t {
+                    Token::Word(ref w) => deny.contains(&normalize(w)),
+                    _ => false,
+                }
+            })
+        }
+    }
+}
+
+/// The fill color for a license: the one given on the command line, or else one picked from a
+/// fixed palette so the same license always gets the same color.
+pub fn color_for(license: Option<&str>, colors: &[(String, String)]) -> String {
+    let license = match license {
+        Some(l) => l,
+        None => return "lightgray".to_owned(),
+    };
+    if let Some(&(_, ref color)) = colors.iter().find(|&&(ref l, _)| l == license) {
+        return color.clone();
+    }
+    let hash = license.bytes().fold(0usize, |h, b| h.wrapping_mul(31).wrapping_add(b as usize));
+    PALETTE[hash % PALETTE.len()].to_owned()
+}
+
+impl<'c> DepGraph<'c> {
+    /// Fills in the license of every crate that doesn't have one yet from its manifest in the
+    /// local registry sources.
+    pub fn find_licenses(&mut self, dirs: &[PathBuf]) {
+        let root_manifest = util::find_manifest_file(&self.cfg.manifest_file).ok();
+        for (id, dep) in self.nodes.iter_mut().enumerate().filter(|&(_, ref d)| d.license.is_none()) {
+            let manifest = match dep.source {
+                Some(ref s) if s.starts_with("path+file://") => {
+                    Some(Path::new(&s[12..]).join("Cargo.toml"))
+                }
+                None if id == 0 => root_manifest.clone(),
+                _ => None,
+            };
+            dep.license = match manifest {
+                Some(m) => util::toml_from_file(&m).ok().and_then(|t| license_from_manifest(&t)),
+                None => find_license(dirs, &dep.name, &dep.ver),
+            };
+        }
+    }
+
+    /// Writes every crate whose license is denied along with how it's reached from the root,
+    /// and returns how many there were.
+    pub fn write_license_report<W: Write>(&self, w: &mut W) -> CliResult<usize> {
+        let mut found = 0;
+        for (id, dep) in self.nodes.iter().enumerate() {
+            let license = match dep.license {
+                Some(ref l) if is_denied(l, &self.cfg.deny_licenses) => l,
+                _ => continue,
+            };
+            found += 1;
+            try!(writeln!(w, "{} v{} ({})", dep.name, dep.ver, license));
+            if let Some(path) = self.path_from_root(id) {
+                let names = path.iter().map(|&n| &*self.nodes[n].name).collect::<Vec<_>>();
+                try!(writeln!(w, "    {}", names.join(" -> ")));
+            }
+        }
+
+        let unknown = self.nodes.iter().filter(|d| d.license.is_none()).count();
+        if unknown > 0 {
+            try!(writeln!(w, "{} crate(s) with an unknown license", unknown));
+        }
+        Ok(found)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use config::{ConfigBuilder, External};
+    use graph::tests::graph;
+    use super::{is_denied, Expr, Parser};
+
+    fn denied(expr: &str, deny: &[&str]) -> bool {
+        is_denied(expr, &deny.iter().map(|d| d.to_string()).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        assert_eq!(Parser::parse("MIT OR Apache-2.0 AND GPL-3.0"),
+                   Ok(Expr::Or(Box::new(Expr::License("mit".to_owned())),
+                               Box::new(Expr::And(Box::new(Expr::License("apache-2.0".to_owned())),
+                                                  Box::new(Expr::License("gpl-3.0".to_owned())))))));
+    }
+
+    #[test]
+    fn respects_parentheses() {
+        assert!(denied("(MIT OR Apache-2.0) AND GPL-3.0", &["GPL-3.0"]));
+        assert!(!denied("MIT OR (Apache-2.0 AND GPL-3.0)", &["GPL-3.0"]));
+        assert!(!denied("MIT OR Apache-2.0 AND GPL-3.0", &["GPL-3.0"]));
+    }
+
+    #[test]
+    fn or_is_denied_only_when_every_alternative_is() {
+        assert!(!denied("MIT OR GPL-3.0", &["GPL-3.0"]));
+        assert!(denied("MIT OR GPL-3.0", &["GPL-3.0", "MIT"]));
+        assert!(!denied("MIT/GPL-3.0", &["GPL-3.0"]));
+        assert!(denied("MIT AND GPL-3.0", &["GPL-3.0"]));
+    }
+
+    #[test]
+    fn matches_license_variants() {
+        assert!(denied("GPL-3.0-only", &["GPL-3.0"]));
+        assert!(denied("GPL-3.0-or-later", &["GPL-3.0"]));
+        assert!(denied("GPL-3.0+", &["GPL-3.0"]));
+        assert!(denied("gpl-3.0", &["GPL-3.0"]));
+        assert!(denied("GPL-3.0", &["GPL-3.0-only"]));
+        assert!(!denied("LGPL-3.0", &["GPL-3.0"]));
+    }
+
+    #[test]
+    fn ignores_with_exceptions() {
+        assert!(denied("GPL-2.0 WITH Classpath-exception-2.0", &["GPL-2.0"]));
+        assert!(!denied("MIT OR GPL-2.0 WITH Classpath-exception-2.0", &["GPL-2.0"]));
+        assert!(!denied("Apache-2.0 WITH LLVM-exception", &["LLVM-exception"]));
+    }
+
+    #[test]
+    fn operators_are_case_insensitive() {
+        assert!(!denied("MIT or GPL-3.0", &["GPL-3.0"]));
+        assert!(denied("MIT and GPL-3.0", &["GPL-3.0"]));
+        assert!(denied("GPL-2.0 with Classpath-exception-2.0", &["GPL-2.0"]));
+    }
+
+    #[test]
+    fn invalid_expressions_are_denied_if_they_name_a_denied_license() {
+        assert_eq!(Parser::parse("MIT OR"), Err(()));
+        assert_eq!(Parser::parse("(MIT"), Err(()));
+        assert!(denied("MIT OR (GPL-3.0", &["GPL-3.0"]));
+        assert!(!denied("MIT OR (Apache-2.0", &["GPL-3.0"]));
+    }
+
+    #[test]
+    fn nothing_is_denied_without_a_deny_list() {
+        assert!(!denied("GPL-3.0", &[]));
+    }
+
+    #[test]
+    fn report_includes_transitive_crates() {
+        let cfg = ConfigBuilder::new()
+                      .external(External::Shallow)
+                      .deny_license("GPL-3.0")
+                      .build();
+        let mut dg = graph(&cfg, &[("app", "a"), ("a", "b")]);
+        for dep in &mut dg.nodes {
+            dep.license = Some("MIT".to_owned());
+        }
+        dg.nodes[1].source = Some("registry+https://github.com/rust-lang/crates.io-index".to_owned());
+        dg.nodes[2].source = dg.nodes[1].source.clone();
+        dg.nodes[2].license = Some("(MIT OR Apache-2.0) AND GPL-3.0-or-later".to_owned());
+        dg.normalize();
+        let mut out = vec![];
+        assert_eq!(dg.write_license_report(&mut out).unwrap(), 1);
+        assert_eq!(String::from_utf8(out).unwrap(),
+                   "b v1.0.0 ((MIT OR Apache-2.0) AND GPL-3.0-or-later)\n    app -> a -> b\n");
+    }
+}
@@ -26,6 +26,10 @@
 //! **NOTE:** It's also possible to run `cargo graph [options] | dot [options]
 //! > [file]` instead of individual commands
 //!
+//! **NOTE:** `cargo graph [options] --output [file]` runs `dot` itself,
+//! using the extension of the file as the image format (ex. `--output
+//! cargo-count.svg`)
+//!
 //! The above commands would produce the following graph:
 //!
 //! ![cargo-graph dependencies](rainbow-graph.png)
@@ -121,10 +125,18 @@
 //!
 //! ```ignore
 //! USAGE:
-//!     cargo graph [FLAGS] [OPTIONS]
+//!     cargo graph [FLAGS] [OPTIONS] [SUBCOMMAND]
 //!
 //! FLAGS:
 //!     -h, --help       Prints help information
+//!         --group-internal      Draw first-party (path) crates bold and in their own cluster
+//!     -I, --include-versions    Include the dependency version on nodes
+//!         --merge-versions      Show each crate once, with all of its versions on a single node
+//!         --reduce              Remove edges that are implied by a longer path (transitive reduction)
+//!         --registry-sources    Also read dependency kinds from the crates in the cargo registry
+//!                               sources
+//!         --watch               Regenerate the output whenever the lock file or a manifest changes,
+//!                               including those of path crates (needs --dot-file or --output)
 //!     -V, --version    Prints version information
 //!
 //! OPTIONS:
@@ -147,6 +159,12 @@
 //! to 'round')
 //! [values: box round diamond
 //! triangle]
+//! --color <WHEN>                   When to color the tree output,
+//! auto only colors it on a terminal (Defaults to 'auto') [values:
+//! auto always never]
+//! --color-by <WHAT>                What decides the fill color of
+//! nodes (Defaults to 'kind') [values: kind license fan-in
+//! descendants depth]
 //! --dev-color <COLOR>              Color for dev deps (Defaults to
 //! 'black')
 //! [values: blue black yellow purple
@@ -167,10 +185,36 @@
 //! [values: box round diamond
 //! triangle]
 //!         --dot-file <FILE>                Output file (Default to stdout)
+//! --edge-attr <KEY=VALUE>...       Default DOT attribute for all
+//! edges (ex. arrowsize=0.5)
+//! --edge-labels <LABEL>            What to label edges from the root
+//! crate with, req is the version requirement, or the repository or
+//! path of git and path deps (Defaults to 'none') [values: none req]
+//! --external <MODE>                How to draw third-party crates
+//! (Defaults to 'full') [values: full muted shallow]
+//! --format <FORMAT>                Output format (Defaults to 'dot')
+//! [values: dot tree html graphml gexf plantuml d2 csv tsv]
+//! --graph-attr <KEY=VALUE>...      Graph-wide DOT attribute (ex.
+//! splines=ortho)
+//! --highlight <PATTERN>...         Highlight the crates matching
+//! this name (* is a wildcard) and their paths from the root, and dim
+//! the rest of the graph
+//! --license-color <LICENSE=COLOR>...    Fill color for a license
+//! with --color-by license ex. --license-color MIT=green
+//! --links <SITE>                   Add links and tooltips to nodes,
+//! useful for SVG output (Defaults to 'none') [values: none docs.rs
+//! crates.io]
 //! --lock-file <FILE>               Specify location of .lock file
 //! (Default 'Cargo.lock')
 //! --manifest-file <FILE>           Specify location of manifest file
 //! (Default 'Cargo.toml')
+//! --metadata <PATH>                Read dependencies from the output
+//! of `cargo metadata --format-version 1` instead of the manifest and
+//! lock file (use - for stdin)
+//! --node-attr <KEY=VALUE>...       Default DOT attribute for all
+//! nodes (ex. fontname=Helvetica)
+//! --nodes-file <PATH>              Also write a table of nodes to
+//! this file, with --format csv or tsv
 //! --optional-color <COLOR>         Color for optional deps (Defaults
 //! to 'black')
 //! [values: blue black yellow purple
@@ -190,6 +234,45 @@
 //! to 'round')
 //! [values: box round diamond
 //! triangle]
+//! --output <PATH>                  Render an image with GraphViz
+//! `dot`, using the extension as the format (ex. graph.svg), only
+//! with --format dot
+//! --rankdir <DIR>                  Direction of the graph layout
+//! [values: TB LR BT RL]
+//! --size-by <METRIC>               What decides the size of nodes
+//! [values: fan-in descendants depth]
+//! --title <TITLE>                  Title shown on the graph
+//! --unknown-color <COLOR>          Color for deps of an unknown kind
+//! (Defaults to 'black')
+//! [values: blue black yellow purple
+//! green red white orange]
+//! --unknown-deps <true|false>      Should deps of an unknown kind be
+//! in the graph? (Defaults to 'true')
+//! ex. --unknown-deps=false OR
+//! --unknown-deps=no
+//! --unknown-line-color <COLOR>     Line color for deps of an unknown
+//! kind (Defaults to 'black')
+//! [values: blue black yellow purple
+//! green red white orange]
+//! --unknown-line-style <STYLE>     Line style for deps of an unknown
+//! kind (Defaults to 'solid')
+//!                                           [values: solid dotted dashed]
+//! --unknown-shape <SHAPE>          Shape for deps of an unknown kind
+//! (Defaults to 'round')
+//! [values: box round diamond
+//! triangle]
+//! --vendor-dir <PATH>              Read exact dependency kinds from
+//! the crates vendored in this directory (by `cargo vendor`)
+//!
+//! SUBCOMMANDS:
+//! check       Check the graph against the rules of a policy file,
+//! failing if any are broken
+//! layers      Check that no crate depends on a crate of a higher
+//! layer, drawing each layer on its own row
+//! licenses    Report crates using a denied license, along with how
+//! they're reached
+//! path        Show how one crate depends on another, through the
+//! shortest path or all paths
 //! ```
 //!
 //! ## License
@@ -216,39 +299,69 @@
         unused_qualifications)]
 
 extern crate toml;
+extern crate rustc_serialize;
 #[macro_use]
 extern crate clap;
 #[cfg(feature = "color")]
 extern crate ansi_term;
+#[cfg(feature = "color")]
+extern crate atty;
 
+use std::ascii::AsciiExt;
 use std::fs::File;
-use std::io::{self, BufWriter};
-use std::path::Path;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
 
 use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
 
-use error::CliResult;
-use config::Config;
+use error::{CliErrorKind, CliResult};
+use config::{ColorWhen, Command, Config, OutputFormat};
+use graph::DepGraph;
 use project::Project;
 
 #[macro_use]
 mod macros;
+mod csv;
+mod diagram;
 mod error;
 mod graph;
+mod html;
 mod fmt;
+mod layers;
+mod license;
+mod metrics;
+mod paths;
+mod policy;
 mod project;
 mod dep;
 mod config;
+mod tree;
 mod util;
+mod watch;
+mod xml;
 
 static LINE_STYLES: [&'static str; 3] = ["solid", "dotted", "dashed"];
 static COLORS: [&'static str; 8] = ["blue", "black", "yellow", "purple", "green", "red", "white",
                                     "orange"];
 static DEP_SHAPES: [&'static str; 4] = ["box", "round", "diamond", "triangle"];
+static EDGE_LABELS: [&'static str; 2] = ["none", "req"];
+static EXTERNAL: [&'static str; 3] = ["full", "muted", "shallow"];
+static COLOR_BY: [&'static str; 5] = ["kind", "license", "fan-in", "descendants", "depth"];
+static METRICS: [&'static str; 3] = ["fan-in", "descendants", "depth"];
+static COLOR_WHEN: [&'static str; 3] = ["auto", "always", "never"];
+static LINKS: [&'static str; 3] = ["none", "docs.rs", "crates.io"];
+static FORMATS: [&'static str; 9] = ["dot", "tree", "html", "graphml", "gexf", "plantuml",
+                                     "d2", "csv", "tsv"];
+static IMAGE_FORMATS: [&'static str; 8] = ["svg", "png", "pdf", "jpg", "jpeg", "gif", "ps", "eps"];
+static RANK_DIRS: [&'static str; 4] = ["TB", "LR", "BT", "RL"];
 
 fn parse_cli<'a>() -> ArgMatches<'a> {
+    build_cli().get_matches()
+}
+
+fn build_cli<'a, 'b>() -> App<'a, 'b> {
     App::new("cargo-graph")
-        .version(&*format!("v{}", crate_version!()))
+        .version(concat!("v", crate_version!()))
         .bin_name("cargo")
         .settings(&[AppSettings::GlobalVersion, AppSettings::SubcommandRequired])
         .subcommand(SubCommand::with_name("graph")
@@ -256,18 +369,36 @@ fn parse_cli<'a>() -> ArgMatches<'a> {
                         .about("Generate a graph of package dependencies in graphviz format")
                         .args_from_usage("
                             -I, --include-versions 'Include the dependency version on nodes'
+                                --reduce 'Remove edges that are implied by a longer path'
+                                --merge-versions 'Show each crate once, with all of its versions on a single node'
+                                --group-internal 'Draw first-party (path) crates bold and in their own cluster'
+                                --registry-sources 'Also read dependency kinds from the crates in the cargo registry sources'
+                                --watch 'Regenerate the output whenever the lock file or a manifest changes, including those of path crates (needs --dot-file or --output)'
                                 --dot-file [PATH] 'Output file (Default stdout)'
                                 --dev-deps [true|false] 'Should dev deps be included in the graph? (Default false, also allows yes|no)'
                                 --build-deps [true|false] 'Should build deps be in the graph? (Default true, also allows yes|no)'
                                 --optional-deps [true|false] 'Should optional deps be in the graph? (Default true, also allows yes|no)'
+                                --unknown-deps [true|false] 'Should deps of an unknown kind (ex. deps missing from the manifest) be in the graph? (Default true, also allows yes|no)'
                         ")
                         .args(&[
+                            Arg::from_usage("--format [FORMAT] 'Output format'")
+                                .default_value("dot")
+                                .possible_values(&FORMATS),
+                            Arg::from_usage("--output [PATH] 'Render an image with GraphViz `dot`, using the extension as the format (ex. graph.svg)'")
+                                .conflicts_with("dot-file")
+                                .validator(is_image),
+                            Arg::from_usage("--nodes-file [PATH] 'Also write a table of nodes to this file (csv and tsv formats only)'")
+                                .validator(is_file),
                             Arg::from_usage("--lock-file [PATH] 'Specify location of .lock file'")
 								.default_value("Cargo.lock")
                                 .validator(is_file),
                             Arg::from_usage("--manifest-file [PATH] 'Specify location of manifest file'")
 								.default_value("Cargo.toml")
                                 .validator(is_file),
+                            Arg::from_usage("--metadata [PATH] 'Read dependencies from the output of `cargo metadata --format-version 1` instead of the manifest and lock file (use - for stdin)'")
+                                .validator(is_file),
+                            Arg::from_usage("--vendor-dir [PATH] 'Read exact dependency kinds from the crates vendored in this directory (by `cargo vendor`)'")
+                                .validator(is_dir),
                             Arg::from_usage("--build-line-style [STYLE] 'Line style for build deps'")
 								.default_value("solid")
                                 .possible_values(&LINE_STYLES),
@@ -303,8 +434,75 @@ fn parse_cli<'a>() -> ArgMatches<'a> {
                                 .possible_values(&DEP_SHAPES),
                             Arg::from_usage("--dev-color [COLOR] 'Color for dev deps'")
 								.default_value("black")
-                                .possible_values(&COLORS)]))
-        .get_matches()
+                                .possible_values(&COLORS),
+                            Arg::from_usage("--unknown-line-style [STYLE] 'Line style for deps of an unknown kind'")
+								.default_value("solid")
+                                .possible_values(&LINE_STYLES),
+                            Arg::from_usage("--unknown-line-color [COLOR] 'Line color for deps of an unknown kind'")
+								.default_value("black")
+                                .possible_values(&COLORS),
+                            Arg::from_usage("--unknown-shape [SHAPE] 'Shape for deps of an unknown kind'")
+								.default_value("round")
+                                .possible_values(&DEP_SHAPES),
+                            Arg::from_usage("--unknown-color [COLOR] 'Color for deps of an unknown kind'")
+								.default_value("black")
+                                .possible_values(&COLORS),
+                            Arg::from_usage("--edge-labels [LABEL] 'What to label edges from the root crate with'")
+                                .default_value("none")
+                                .possible_values(&EDGE_LABELS),
+                            Arg::from_usage("--external [MODE] 'How to draw third-party crates, shallow only keeps direct deps of first-party crates'")
+                                .default_value("full")
+                                .possible_values(&EXTERNAL),
+                            Arg::from_usage("--color [WHEN] 'When to color the tree output, auto only colors it on a terminal'")
+                                .default_value("auto")
+                                .possible_values(&COLOR_WHEN),
+                            Arg::from_usage("--color-by [WHAT] 'What decides the fill color of nodes'")
+                                .default_value("kind")
+                                .possible_values(&COLOR_BY),
+                            Arg::from_usage("--size-by [METRIC] 'What decides the size of nodes'")
+                                .possible_values(&METRICS),
+                            Arg::from_usage("--license-color [LICENSE=COLOR]... 'Fill color for a license with --color-by license (ex. MIT=green)'")
+                                .number_of_values(1)
+                                .validator(is_attr),
+                            Arg::from_usage("--highlight [PATTERN]... 'Highlight the crates matching this name (* is a wildcard) and their paths from the root'")
+                                .number_of_values(1),
+                            Arg::from_usage("--links [SITE] 'Add links and tooltips to nodes (useful for SVG output)'")
+                                .default_value("none")
+                                .possible_values(&LINKS),
+                            Arg::from_usage("--rankdir [DIR] 'Direction of the graph layout'")
+                                .possible_values(&RANK_DIRS),
+                            Arg::from_usage("--title [TITLE] 'Title shown on the graph'"),
+                            Arg::from_usage("--graph-attr [KEY=VALUE]... 'Graph-wide DOT attribute (ex. splines=ortho)'")
+                                .number_of_values(1)
+                                .validator(is_attr),
+                            Arg::from_usage("--node-attr [KEY=VALUE]... 'Default DOT attribute for all nodes (ex. fontname=Helvetica)'")
+                                .number_of_values(1)
+                                .validator(is_attr),
+                            Arg::from_usage("--edge-attr [KEY=VALUE]... 'Default DOT attribute for all edges (ex. arrowsize=0.5)'")
+                                .number_of_values(1)
+                                .validator(is_attr)])
+                        .subcommand(SubCommand::with_name("licenses")
+                                        .about("Report crates using a denied license, along with how they're reached")
+                                        .arg(Arg::from_usage("--deny [LICENSE]... 'License that must not be used (ex. GPL-3.0)'")
+                                                 .number_of_values(1)))
+                        .subcommand(SubCommand::with_name("check")
+                                        .about("Check the graph against the rules of a policy file, failing if any are broken")
+                                        .arg(Arg::from_usage("--config <PATH> 'Policy file with the rules to check'")
+                                                 .validator(is_file)))
+                        .subcommand(SubCommand::with_name("layers")
+                                        .about("Check that no crate depends on a crate of a higher layer, drawing each layer on its own row")
+                                        .arg(Arg::from_usage("--config <PATH> 'Policy file declaring the [layers]'")
+                                                 .validator(is_file)))
+                        .subcommand(SubCommand::with_name("path")
+                                        .about("Show how one crate depends on another, through the shortest path or all paths")
+                                        .args_from_usage("
+                                            <FROM> 'Crate the paths start from, as a name or name@version'
+                                            <TO> 'Crate the paths lead to, as a name or name@version'
+                                            --all 'Find all paths instead of only the shortest one'
+                                            --full 'Highlight the paths in the full graph instead of only showing the paths'")
+                                        .arg(Arg::from_usage("--limit [N] 'Maximum number of paths to find with --all'")
+                                                 .default_value("100")
+                                                 .validator(is_number))))
 }
 
 fn main() {
@@ -319,23 +517,130 @@ fn main() {
 }
 
 fn execute(cfg: Config) -> CliResult<()> {
-    let project = try!(Project::with_config(&cfg));
-    let graph = try!(project.graph());
+    if !cfg.watch {
+        return generate(&cfg, &mut vec![]);
+    }
+
+    if cfg.dot_file.is_none() && cfg.output.is_none() {
+        return Err(From::from(CliErrorKind::Generic("--watch needs an output file, use \
+                                                     --dot-file or --output"
+                                                        .to_owned())));
+    }
+    let files = match cfg.metadata {
+        Some(ref file) if file == "-" => {
+            return Err(From::from(CliErrorKind::Generic("--watch can't read the metadata from \
+                                                         stdin"
+                                                            .to_owned())));
+        }
+        Some(ref file) => vec![PathBuf::from(file)],
+        None => {
+            vec![try!(util::find_manifest_file(&cfg.manifest_file)),
+                 try!(util::find_manifest_file(&cfg.lock_file))]
+        }
+    };
+    watch::watch(&files, |watched| generate(&cfg, watched))
+}
+
+/// Builds the graph and writes it, or the license report, once. The manifests of path crates,
+/// which can change along with the root's, are added to `watched`.
+fn generate(cfg: &Config, watched: &mut Vec<PathBuf>) -> CliResult<()> {
+    let project = try!(Project::with_config(cfg));
+    let mut graph = try!(project.graph());
+    for dep in &graph.nodes {
+        if let Some(ref src) = dep.source {
+            if src.starts_with("path+file://") {
+                let manifest = Path::new(&src["path+file://".len()..]).join("Cargo.toml");
+                if !watched.contains(&manifest) {
+                    watched.push(manifest);
+                }
+            }
+        }
+    }
+
+    if cfg.command == Command::Graph {
+        return write_graph(graph, cfg);
+    }
+
+    // The paths and checks need every crate and edge, so they don't see the display options
+    graph.normalize();
+    if cfg.command == Command::Path {
+        let paths = try!(graph.find_paths(&cfg.path_from,
+                                          &cfg.path_to,
+                                          cfg.all_paths,
+                                          cfg.path_limit));
+        if cfg.full_graph {
+            graph.highlight_paths(&paths);
+        } else {
+            graph.retain_paths(&paths);
+        }
+        return write_graph(graph, cfg);
+    }
+
+    let (found, what) = {
+        let o = io::stdout();
+        let mut bw = BufWriter::new(o.lock());
+        match cfg.command {
+            Command::Licenses => {
+                (try!(graph.write_license_report(&mut bw)), "crate(s) with a denied license")
+            }
+            Command::Check => {
+                (try!(graph.write_check_report(&mut bw, &cfg.policy)), "policy violation(s)")
+            }
+            Command::Layers => {
+                (try!(graph.write_layers_report(&mut bw)), "edge(s) going to a higher layer")
+            }
+            Command::Graph | Command::Path => unreachable!(),
+        }
+    };
+    if cfg.dot_file.is_some() || cfg.output.is_some() {
+        try!(write_graph(graph, cfg));
+    }
+    if found > 0 {
+        return Err(From::from(CliErrorKind::Violations(format!("found {} {}", found, what))));
+    }
+    Ok(())
+}
+
+fn write_graph(graph: DepGraph, cfg: &Config) -> CliResult<()> {
+    if let Some(ref file) = cfg.output {
+        let mut dot = vec![];
+        try!(graph.render_to(&mut dot));
+        return util::render_image(&dot, file);
+    }
 
     match cfg.dot_file {
         None => {
             let o = io::stdout();
             let mut bw = BufWriter::new(o.lock());
-            graph.render_to(&mut bw)
+            let color = match cfg.color {
+                ColorWhen::Auto => util::stdout_is_tty(),
+                ColorWhen::Always => true,
+                ColorWhen::Never => false,
+            };
+            render(graph, &mut bw, color)
         }
-        Some(file) => {
-            let o = File::create(&Path::new(&file)).expect("Failed to create file");
+        Some(ref file) => {
+            let o = File::create(&Path::new(file)).expect("Failed to create file");
             let mut bw = BufWriter::new(o);
-            graph.render_to(&mut bw)
+            render(graph, &mut bw, false)
         }
     }
 }
 
+fn render<W: Write>(graph: DepGraph, w: &mut W, color: bool) -> CliResult<()> {
+    match graph.cfg.format {
+        OutputFormat::Dot => graph.render_to(w),
+        OutputFormat::Tree => graph.render_tree_to(w, color),
+        OutputFormat::Html => graph.render_html_to(w),
+        OutputFormat::Graphml => graph.render_graphml_to(w),
+        OutputFormat::Gexf => graph.render_gexf_to(w),
+        OutputFormat::Plantuml => graph.render_plantuml_to(w),
+        OutputFormat::D2 => graph.render_d2_to(w),
+        OutputFormat::Csv => graph.render_table_to(w, ','),
+        OutputFormat::Tsv => graph.render_table_to(w, '\t'),
+    }
+}
+
 fn is_file(s: String) -> Result<(), String> {
     let p = Path::new(&*s);
     if let None = p.file_name() {
@@ -343,3 +648,42 @@ fn is_file(s: String) -> Result<(), String> {
     }
     Ok(())
 }
+
+fn is_dir(s: String) -> Result<(), String> {
+    if !Path::new(&*s).is_dir() {
+        return Err(format!("'{}' isn't a directory", &*s));
+    }
+    Ok(())
+}
+
+fn is_image(s: String) -> Result<(), String> {
+    try!(is_file(s.clone()));
+    match Path::new(&*s).extension().and_then(|e| e.to_str()) {
+        Some(ext) if IMAGE_FORMATS.contains(&&*ext.to_ascii_lowercase()) => Ok(()),
+        _ => {
+            Err(format!("'{}' doesn't have a supported image extension [values: {}]",
+                        &*s,
+                        IMAGE_FORMATS.join(", ")))
+        }
+    }
+}
+
+fn is_attr(s: String) -> Result<(), String> {
+    // Keys are written as they are, so they have to be plain DOT identifiers
+    fn is_ident(key: &str) -> bool {
+        key.chars().next().map_or(false, |c| c.is_ascii_alphabetic() || c == '_') &&
+        key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    }
+
+    match s.find('=') {
+        Some(i) if is_ident(&s[..i]) => Ok(()),
+        _ => Err(format!("'{}' isn't a valid attribute, use KEY=VALUE", &*s)),
+    }
+}
+
+fn is_number(s: String) -> Result<(), String> {
+    match s.parse::<usize>() {
+        Ok(_) => Ok(()),
+        Err(_) => Err(format!("'{}' isn't a valid number", &*s)),
+    }
+}
@@ -0,0 +1,237 @@
+use std::cmp;
+use std::collections::VecDeque;
+
+use config::{ColorBy, Metric};
+use graph::{DepGraph, Ed, Nd};
+
+impl<'c> DepGraph<'c> {
+    /// Returns the distance of every node from `from`, or `None` for the nodes it can't reach.
+    fn distances(&self, adj: &[Vec<Nd>], from: Nd) -> Vec<Option<usize>> {
+        let mut dist = vec![None; self.nodes.len()];
+        let mut queue = VecDeque::new();
+        dist[from] = Some(0);
+        queue.push_back(from);
+        while let Some(n) = queue.pop_front() {
+            for &child in &adj[n] {
+                if dist[child].is_none() {
+                    dist[child] = Some(dist[n].unwrap() + 1);
+                    queue.push_back(child);
+                }
+            }
+        }
+        dist
+    }
+
+    /// Returns the number of crates each node reaches, not counting itself. The strongly
+    /// connected components are found with Tarjan's algorithm, which lists a component after
+    /// every component it reaches, so each one only has to merge the sets of its children.
+    fn descendants(&self, adj: &[Vec<Nd>]) -> Vec<usize> {
+        struct Tarjan<'a> {
+            adj: &'a [Vec<Nd>],
+            index: Vec<Option<usize>>,
+            low: Vec<usize>,
+            on_stack: Vec<bool>,
+            stack: Vec<Nd>,
+            next: usize,
+            comps: Vec<Vec<Nd>>,
+            comp_of: Vec<usize>,
+        }
+
+        fn visit(t: &mut Tarjan, n: Nd) {
+            t.index[n] = Some(t.next);
+            t.low[n] = t.next;
+            t.next += 1;
+            t.stack.push(n);
+            t.on_stack[n] = true;
+            for i in 0..t.adj[n].len() {
+                let child = t.adj[n][i];
+                match t.index[child] {
+                    None => {
+                        visit(t, child);
+                        t.low[n] = cmp::min(t.low[n], t.low[child]);
+                    }
+                    Some(index) if t.on_stack[child] => t.low[n] = cmp::min(t.low[n], index),
+                    Some(_) => (),
+                }
+            }
+            if Some(t.low[n]) == t.index[n] {
+                let mut comp = vec![];
+                loop {
+                    let m = t.stack.pop().unwrap();
+                    t.on_stack[m] = false;
+                    t.comp_of[m] = t.comps.len();
+                    comp.push(m);
+                    if m == n {
+                        break;
+                    }
+                }
+                t.comps.push(comp);
+            }
+        }
+
+        let len = self.nodes.len();
+        let mut t = Tarjan {
+            adj: adj,
+            index: vec![None; len],
+            low: vec![0; len],
+            on_stack: vec![false; len],
+            stack: vec![],
+            next: 0,
+            comps: vec![],
+            comp_of: vec![0; len],
+        };
+        for n in 0..len {
+            if t.index[n].is_none() {
+                visit(&mut t, n);
+            }
+        }
+
+        // The crates each component reaches, itself included, as a bit set
+        let words = (len + 63) / 64;
+        let mut reach: Vec<Vec<u64>> = Vec::with_capacity(t.comps.len());
+        for (c, comp) in t.comps.iter().enumerate() {
+            let mut bits = vec![0u64; words];
+            for &n in comp {
+                bits[n / 64] |= 1 << (n % 64);
+                for &child in &adj[n] {
+                    let other = t.comp_of[child];
+                    if other != c {
+                        for (b, &o) in bits.iter_mut().zip(&reach[other]) {
+                            *b |= o;
+                        }
+                    }
+                }
+            }
+            reach.push(bits);
+        }
+        (0..len)
+            .map(|n| {
+                reach[t.comp_of[n]].iter().map(|b| b.count_ones() as usize).sum::<usize>() - 1
+            })
+            .collect()
+    }
+
+    /// Computes the value of a metric for every node.
+    pub fn metric(&self, metric: Metric) -> Vec<usize> {
+        let mut adj = vec![vec![]; self.nodes.len()];
+        for &Ed(idl, idr, _, _) in &self.edges {
+            if !adj[idl].contains(&idr) {
+                adj[idl].push(idr);
+            }
+        }
+
+        match metric {
+            Metric::FanIn => {
+                let mut fan_in = vec![0; self.nodes.len()];
+                for &child in adj.iter().flat_map(|children| children.iter()) {
+                    fan_in[child] += 1;
+                }
+                fan_in
+            }
+            Metric::Descendants => self.descendants(&adj),
+            Metric::Depth => {
+                self.distances(&adj, 0).into_iter().map(|d| d.unwrap_or(0)).collect()
+            }
+        }
+    }
+
+    /// Returns the values of a metric scaled from 0 to 1.
+    fn scaled_metric(&self, metric: Metric) -> Vec<f64> {
+        let values = self.metric(metric);
+        let max = values.iter().cloned().max().unwrap_or(0);
+        values.into_iter()
+              .map(|v| if max == 0 { 0.0 } else { v as f64 / max as f64 })
+              .collect()
+    }
+
+    /// Sets the size and fill color of the nodes from the metrics chosen with `--size-by` and
+    /// `--color-by`.
+    pub fn apply_metrics(&mut self) {
+        if let Some(metric) = self.cfg.size_by {
+            let sizes = self.scaled_metric(metric);
+            for (dep, size) in self.nodes.iter_mut().zip(sizes) {
+                dep.size = Some(size);
+            }
+        }
+        if let ColorBy::Metric(metric) = self.cfg.color_by {
+            let heats = self.scaled_metric(metric);
+            for (dep, heat) in self.nodes.iter_mut().zip(heats) {
+                dep.heat = Some(heat);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use config::{ColorBy, ConfigBuilder, Metric};
+    use graph::tests::graph;
+
+    static EDGES: [(&'static str, &'static str); 7] = [("app", "a"),
+                                                       ("app", "b"),
+                                                       ("a", "c"),
+                                                       ("b", "c"),
+                                                       ("c", "d"),
+                                                       ("d", "c"),
+                                                       ("b", "e")];
+
+    #[test]
+    fn fan_in_counts_parents() {
+        let cfg = ConfigBuilder::new().build();
+        let dg = graph(&cfg, &EDGES);
+        // app, a, b, c, d, e
+        assert_eq!(dg.metric(Metric::FanIn), [0, 1, 1, 3, 1, 1]);
+    }
+
+    #[test]
+    fn descendants_count_every_crate_reached() {
+        let cfg = ConfigBuilder::new().build();
+        let dg = graph(&cfg, &EDGES);
+        // c and d reach each other through the cycle
+        assert_eq!(dg.metric(Metric::Descendants), [5, 2, 3, 1, 1, 0]);
+    }
+
+    #[test]
+    fn descendants_match_a_search_from_every_node() {
+        let cfg = ConfigBuilder::new().build();
+        let mut edges = vec![];
+        let names = (0..150).map(|i| format!("c{}", i)).collect::<Vec<_>>();
+        for i in 0..names.len() {
+            for &j in &[i * 7 % 150, (i * 13 + 5) % 150, (i + 1) % 150] {
+                if (i + j) % 3 != 0 {
+                    edges.push((&*names[i], &*names[j]));
+                }
+            }
+        }
+        let dg = graph(&cfg, &edges);
+        let mut adj = vec![vec![]; dg.nodes.len()];
+        for ed in &dg.edges {
+            adj[ed.0].push(ed.1);
+        }
+        let expected = (0..dg.nodes.len())
+                           .map(|n| dg.distances(&adj, n).iter().filter(|d| d.is_some()).count() - 1)
+                           .collect::<Vec<_>>();
+        assert_eq!(dg.metric(Metric::Descendants), expected);
+    }
+
+    #[test]
+    fn depth_is_the_distance_from_the_root() {
+        let cfg = ConfigBuilder::new().build();
+        let dg = graph(&cfg, &EDGES);
+        assert_eq!(dg.metric(Metric::Depth), [0, 1, 1, 2, 3, 2]);
+    }
+
+    #[test]
+    fn metrics_are_scaled_from_0_to_1() {
+        let cfg = ConfigBuilder::new()
+                      .size_by(Metric::FanIn)
+                      .color_by(ColorBy::Metric(Metric::Depth))
+                      .build();
+        let mut dg = graph(&cfg, &EDGES);
+        dg.apply_metrics();
+        assert_eq!(dg.nodes[3].size, Some(1.0));
+        assert_eq!(dg.nodes[0].size, Some(0.0));
+        assert_eq!(dg.nodes[4].heat, Some(1.0));
+        assert_eq!(dg.nodes[1].heat, Some(1.0 / 3.0));
+    }
+}
@@ -0,0 +1,253 @@
+use error::{CliErrorKind, CliResult};
+use graph::{DepGraph, Ed, Nd};
+
+impl<'c> DepGraph<'c> {
+    /// Returns the nodes matching `spec`, which is either a crate name or `name@version`.
+    pub fn find_nodes(&self, spec: &str) -> Vec<Nd> {
+        let (name, ver) = match spec.find('@') {
+            Some(i) => (&spec[..i], Some(&spec[i + 1..])),
+            None => (spec, None),
+        };
+        (0..self.nodes.len())
+            .filter(|&id| {
+                let dep = &self.nodes[id];
+                dep.name == name &&
+                ver.map_or(true, |v| dep.ver == v || dep.merged_vers.iter().any(|m| m == v))
+            })
+            .collect()
+    }
+
+    /// Returns every path without cycles from `from` to any of `to`, stopping after `limit` paths.
+    pub fn all_paths(&self, from: Nd, to: &[Nd], limit: usize) -> Vec<Vec<Nd>> {
+        struct Search<'a> {
+            adj: Vec<Vec<Nd>>,
+            to: &'a [Nd],
+            reaches: Vec<bool>,
+            on_path: Vec<bool>,
+            path: Vec<Nd>,
+            paths: Vec<Vec<Nd>>,
+            limit: usize,
+        }
+
+        fn walk(s: &mut Search, n: Nd) {
+            if s.paths.len() >= s.limit {
+                return;
+            }
+            if s.to.contains(&n) {
+                s.paths.push(s.path.clone());
+                return;
+            }
+            for i in 0..s.adj[n].len() {
+                let child = s.adj[n][i];
+                if s.on_path[child] || !s.reaches[child] {
+                    continue;
+                }
+                s.on_path[child] = true;
+                s.path.push(child);
+                walk(s, child);
+                s.path.pop();
+                s.on_path[child] = false;
+            }
+        }
+
+        let mut adj = vec![vec![]; self.nodes.len()];
+        let mut parents = vec![vec![]; self.nodes.len()];
+        for &Ed(idl, idr, _, _) in &self.edges {
+            adj[idl].push(idr);
+            parents[idr].push(idl);
+        }
+        // Only go through nodes that lead to one of the targets
+        let mut reaches = vec![false; self.nodes.len()];
+        let mut stack = to.to_vec();
+        while let Some(n) = stack.pop() {
+            if !reaches[n] {
+                reaches[n] = true;
+                stack.extend(parents[n].iter().cloned());
+            }
+        }
+
+        let mut search = Search {
+            adj: adj,
+            to: to,
+            reaches: reaches,
+            on_path: vec![false; self.nodes.len()],
+            path: vec![from],
+            paths: vec![],
+            limit: limit,
+        };
+        search.on_path[from] = true;
+        walk(&mut search, from);
+        search.paths
+    }
+
+    /// Finds the shortest path from `from` to `to`, or all of them (up to `limit`) when `all` is
+    /// set. Either end can be a crate name or `name@version`.
+    pub fn find_paths(&self,
+                      from: &str,
+                      to: &str,
+                      all: bool,
+                      limit: usize)
+                      -> CliResult<Vec<Vec<Nd>>> {
+        let from_ids = self.find_nodes(from);
+        if from_ids.is_empty() {
+            return Err(From::from(CliErrorKind::Generic(format!("no crate named {}", from))));
+        }
+        if from_ids.len() > 1 {
+            let ids = from_ids.iter().map(|&id| self.node_id(id)).collect::<Vec<_>>();
+            return Err(From::from(CliErrorKind::Generic(format!("{} is ambiguous, use one of: {}",
+                                                                from,
+                                                                ids.join(", ")))));
+        }
+        let from_id = from_ids[0];
+        let to_ids = self.find_nodes(to);
+        if to_ids.is_empty() {
+            return Err(From::from(CliErrorKind::Generic(format!("no crate named {}", to))));
+        }
+
+        let paths = if all {
+            self.all_paths(from_id, &to_ids, limit)
+        } else {
+            to_ids.iter()
+                  .filter_map(|&id| self.shortest_path(from_id, id, |_| false))
+                  .min_by_key(|path| path.len())
+                  .into_iter()
+                  .collect()
+        };
+        if paths.is_empty() {
+            return Err(From::from(CliErrorKind::Generic(format!("{} doesn't depend on {}",
+                                                                from,
+                                                                to))));
+        }
+        Ok(paths)
+    }
+
+    /// Highlights the nodes and edges of `paths` and dims the rest of the graph.
+    pub fn highlight_paths(&mut self, paths: &[Vec<Nd>]) {
+        self.highlight(paths.iter().flat_map(|p| p.iter()).cloned());
+        self.highlight_edges(paths.iter().flat_map(|p| p.windows(2)).map(|s| (s[0], s[1])));
+    }
+
+    /// Keeps only the nodes and edges of `paths`, which must all start at the same node. That
+    /// node becomes the root.
+    pub fn retain_paths(&mut self, paths: &[Vec<Nd>]) {
+        let mut on_paths = vec![false; self.nodes.len()];
+        let mut steps = vec![];
+        for path in paths {
+            for &id in path {
+                on_paths[id] = true;
+            }
+            for step in path.windows(2) {
+                steps.push((step[0], step[1]));
+            }
+        }
+        self.edges.retain(|&Ed(idl, idr, _, _)| steps.contains(&(idl, idr)));
+
+        // Keep the start of the paths at index 0, since that's where the root lives
+        let start = paths[0][0];
+        let (name, ver) = (self.nodes[start].name.clone(), self.nodes[start].ver.clone());
+        on_paths.swap(0, start);
+        self.set_root(&name, &ver);
+        for id in (1..self.nodes.len()).rev() {
+            if !on_paths[id] {
+                self.remove(id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use config::ConfigBuilder;
+    use graph::DepGraph;
+    use graph::tests::{edges, graph, names};
+    use graph::Nd;
+
+    fn show(dg: &DepGraph, paths: &[Vec<Nd>]) -> Vec<String> {
+        paths.iter()
+             .map(|p| p.iter().map(|&n| &*dg.nodes[n].name).collect::<Vec<_>>().join(" -> "))
+             .collect()
+    }
+
+    static WEB: [(&'static str, &'static str); 5] = [("web", "rustls"),
+                                                     ("rustls", "ring"),
+                                                     ("web", "ring"),
+                                                     ("web", "hyper"),
+                                                     ("hyper", "rustls")];
+
+    #[test]
+    fn finds_nodes_by_name_and_version() {
+        let cfg = ConfigBuilder::new().build();
+        let dg = graph(&cfg, &[("app", "log@0.3.0"), ("app", "log@0.4.0")]);
+        assert_eq!(dg.find_nodes("log").len(), 2);
+        assert_eq!(dg.find_nodes("log@0.4.0"), [2]);
+        assert!(dg.find_nodes("log@0.5.0").is_empty());
+    }
+
+    #[test]
+    fn finds_the_shortest_path() {
+        let cfg = ConfigBuilder::new().build();
+        let dg = graph(&cfg, &WEB);
+        let paths = dg.find_paths("web", "ring", false, 100).unwrap();
+        assert_eq!(show(&dg, &paths), ["web -> ring"]);
+    }
+
+    #[test]
+    fn finds_all_paths_up_to_the_limit() {
+        let cfg = ConfigBuilder::new().build();
+        let dg = graph(&cfg, &WEB);
+        let mut paths = show(&dg, &dg.find_paths("web", "ring", true, 100).unwrap());
+        paths.sort();
+        assert_eq!(paths,
+                   ["web -> hyper -> rustls -> ring", "web -> ring", "web -> rustls -> ring"]);
+        assert_eq!(dg.find_paths("web", "ring", true, 2).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn all_paths_survive_cycles() {
+        let cfg = ConfigBuilder::new().build();
+        let dg = graph(&cfg, &[("a", "b"), ("b", "a"), ("b", "c")]);
+        assert_eq!(show(&dg, &dg.find_paths("a", "c", true, 100).unwrap()), ["a -> b -> c"]);
+    }
+
+    #[test]
+    fn reports_missing_or_unreachable_crates() {
+        let cfg = ConfigBuilder::new().build();
+        let dg = graph(&cfg, &[("app", "a"), ("app", "b"), ("a", "x@1.0.0"), ("b", "x@2.0.0")]);
+        assert!(dg.find_paths("app", "missing", false, 100).is_err());
+        assert!(dg.find_paths("a", "b", false, 100).is_err());
+        assert!(dg.find_paths("x", "app", false, 100).is_err());
+        assert_eq!(show(&dg, &dg.find_paths("b", "x", false, 100).unwrap()), ["b -> x"]);
+    }
+
+    #[test]
+    fn retain_paths_keeps_only_the_paths() {
+        let cfg = ConfigBuilder::new().build();
+        let mut dg = graph(&cfg,
+                           &[("app", "web"), ("web", "rustls"), ("rustls", "ring"), ("web", "ring"),
+                             ("web", "log"), ("app", "ring")]);
+        let paths = dg.find_paths("web", "ring", true, 100).unwrap();
+        dg.retain_paths(&paths);
+        assert_eq!(names(&dg), ["web", "rustls", "ring"]);
+        assert_eq!(edges(&dg), ["rustls -> ring", "web -> ring", "web -> rustls"]);
+    }
+
+    #[test]
+    fn highlights_only_the_edges_of_the_paths() {
+        let cfg = ConfigBuilder::new().merge_versions(true).build();
+        let mut dg = graph(&cfg,
+                           &[("web", "rustls"), ("rustls", "ring@2.0.0"), ("web", "ring@1.0.0"),
+                             ("web", "hyper"), ("hyper", "rustls")]);
+        let paths = dg.find_paths("web", "ring@2.0.0", true, 1).unwrap();
+        assert_eq!(show(&dg, &paths), ["web -> rustls -> ring"]);
+        dg.highlight_paths(&paths);
+        dg.prepare();
+        let highlighted = dg.edges
+                            .iter()
+                            .filter(|ed| dg.is_highlighted(ed))
+                            .map(|ed| format!("{} -> {}", dg.nodes[ed.0].name, dg.nodes[ed.1].name))
+                            .collect::<Vec<_>>();
+        // web -> ring joins two highlighted crates, but isn't on the path
+        assert_eq!(highlighted, ["web -> rustls", "rustls -> ring"]);
+        assert!(dg.nodes.iter().find(|d| d.name == "hyper").unwrap().dimmed);
+    }
+}
@@ -0,0 +1,270 @@
+use std::io::Write;
+
+use toml::{Table, Value};
+
+use error::{CliError, CliErrorKind, CliResult};
+use graph::DepGraph;
+use layers::{self, Layer};
+use util;
+
+/// Rules the dependency graph must follow, read from a TOML file like:
+///
+/// ```toml
+/// banned = ["openssl-sys"]      # crates that must not appear
+/// allow-git = false             # whether crates may come from git
+/// max-depth = 12                # how many levels from the root a crate may be
+/// max-crates = 300              # number of crates, not counting the root
+///
+/// [only-via]
+/// ring = ["rustls"]             # ring may only be reached through rustls
+///
+/// [layers]                      # crates must not depend on crates of a higher layer
+/// order = "core < domain < app"
+/// ```
+#[derive(Debug, Clone)]
+pub struct Policy {
+    pub banned: Vec<String>,
+    pub only_via: Vec<(String, Vec<String>)>,
+    pub allow_git: bool,
+    pub max_depth: Option<usize>,
+    pub max_crates: Option<usize>,
+    pub layers: Vec<Layer>,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Policy {
+            banned: vec![],
+            only_via: vec![],
+            allow_git: true,
+            max_depth: None,
+            max_crates: None,
+            layers: vec![],
+        }
+    }
+}
+
+fn invalid(what: &str) -> CliError {
+    From::from(CliErrorKind::Generic(format!("invalid policy file: {}", what)))
+}
+
+fn names(value: &Value, key: &str) -> CliResult<Vec<String>> {
+    let names = try!(value.as_slice()
+                          .ok_or_else(|| invalid(&format!("'{}' must be a list of crates", key))));
+    names.iter()
+         .map(|n| {
+             n.as_str()
+              .map(ToOwned::to_owned)
+              .ok_or_else(|| invalid(&format!("'{}' must be a list of crates", key)))
+         })
+         .collect()
+}
+
+fn limit(value: &Value, key: &str) -> CliResult<usize> {
+    match value.as_integer() {
+        Some(n) if n >= 0 => Ok(n as usize),
+        _ => Err(invalid(&format!("'{}' must be a positive number", key))),
+    }
+}
+
+impl Policy {
+    pub fn from_file(file: &str) -> CliResult<Self> {
+        let toml = try!(util::toml_from_file(file));
+        Policy::from_toml(&toml)
+    }
+
+    pub fn from_toml(toml: &Table) -> CliResult<Self> {
+        let mut policy = Policy::default();
+        for (key, value) in toml {
+            match &**key {
+                "banned" => policy.banned = try!(names(value, key)),
+                "allow-git" => {
+                    policy.allow_git = try!(value.as_bool()
+                                                 .ok_or_else(|| invalid("'allow-git' must be a \
+                                                                         boolean")))
+                }
+                "max-depth" => policy.max_depth = Some(try!(limit(value, key))),
+                "max-crates" => policy.max_crates = Some(try!(limit(value, key))),
+                "only-via" => {
+                    let table = try!(value.as_table()
+                                          .ok_or_else(|| invalid("'only-via' must be a table")));
+                    for (name, via) in table {
+                        policy.only_via.push((name.clone(), try!(names(via, name))));
+                    }
+                }
+                "layers" => policy.layers = try!(layers::from_toml(value)),
+                _ => return Err(invalid(&format!("unknown key '{}'", key))),
+            }
+        }
+        Ok(policy)
+    }
+}
+
+impl<'c> DepGraph<'c> {
+    fn write_path<W: Write>(&self, w: &mut W, path: &[usize]) -> CliResult<()> {
+        let names = path.iter().map(|&n| &*self.nodes[n].name).collect::<Vec<_>>();
+        try!(writeln!(w, "    {}", names.join(" -> ")));
+        Ok(())
+    }
+
+    /// Writes every violation of the policy, along with the path that leads to it, and returns
+    /// how many were found.
+    pub fn write_check_report<W: Write>(&self, w: &mut W, policy: &Policy) -> CliResult<usize> {
+        let mut found = 0;
+        for (id, dep) in self.nodes.iter().enumerate().skip(1) {
+            if policy.banned.contains(&dep.name) {
+                found += 1;
+                try!(writeln!(w, "banned crate {} v{}", dep.name, dep.ver));
+                if let Some(path) = self.path_from_root(id) {
+                    try!(self.write_path(w, &path));
+                }
+            }
+
+            if !policy.allow_git && dep.source.as_ref().map_or(false, |s| s.starts_with("git+")) {
+                found += 1;
+                try!(writeln!(w, "{} v{} comes from git", dep.name, dep.ver));
+                if let Some(path) = self.path_from_root(id) {
+                    try!(self.write_path(w, &path));
+                }
+            }
+
+            for &(ref name, ref via) in &policy.only_via {
+                if *name != dep.name {
+                    continue;
+                }
+                let nodes = &self.nodes;
+                if let Some(path) = self.shortest_path(0, id, |n| via.contains(&nodes[n].name)) {
+                    found += 1;
+                    try!(writeln!(w,
+                                  "{} v{} is reached without going through {}",
+                                  dep.name,
+                                  dep.ver,
+                                  via.join(" or ")));
+                    try!(self.write_path(w, &path));
+                }
+            }
+
+            if let Some(max) = policy.max_depth {
+                if let Some(path) = self.path_from_root(id) {
+                    if path.len() - 1 > max {
+                        found += 1;
+                        try!(writeln!(w,
+                                      "{} v{} is {} levels deep, over the maximum of {}",
+                                      dep.name,
+                                      dep.ver,
+                                      path.len() - 1,
+                                      max));
+                        try!(self.write_path(w, &path));
+                    }
+                }
+            }
+        }
+
+        if let Some(max) = policy.max_crates {
+            let crates = self.nodes.len() - 1;
+            if crates > max {
+                found += 1;
+                try!(writeln!(w, "{} crates are used, over the maximum of {}", crates, max));
+            }
+        }
+
+        found += try!(self.write_layers_report(w));
+        Ok(found)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use toml::Parser;
+
+    use config::ConfigBuilder;
+    use graph::tests::graph;
+    use super::Policy;
+
+    fn policy(s: &str) -> Policy {
+        Policy::from_toml(&Parser::new(s).parse().unwrap()).unwrap()
+    }
+
+    fn check(policy: Policy, edges: &[(&str, &str)]) -> (usize, String) {
+        let cfg = ConfigBuilder::new().policy(policy).build();
+        let mut dg = graph(&cfg, edges);
+        dg.normalize();
+        let mut out = vec![];
+        let found = dg.write_check_report(&mut out, &cfg.policy).unwrap();
+        (found, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn parses_rules() {
+        let p = policy("banned = [\"openssl-sys\"]\nallow-git = false\nmax-depth = 3\n\
+                        max-crates = 10\n[only-via]\nring = [\"rustls\"]\n");
+        assert_eq!(p.banned, ["openssl-sys"]);
+        assert!(!p.allow_git);
+        assert_eq!(p.max_depth, Some(3));
+        assert_eq!(p.max_crates, Some(10));
+        assert_eq!(p.only_via, [("ring".to_owned(), vec!["rustls".to_owned()])]);
+    }
+
+    #[test]
+    fn rejects_invalid_rules() {
+        for s in &["banned = \"openssl\"", "max-depth = -1", "allow-git = 1", "only-via = 1",
+                   "unknown = 1"] {
+            assert!(Policy::from_toml(&Parser::new(s).parse().unwrap()).is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn reports_banned_crates_with_their_path() {
+        let (found, out) = check(policy("banned = [\"openssl-sys\"]"),
+                                 &[("app", "native-tls"), ("native-tls", "openssl-sys")]);
+        assert_eq!(found, 1);
+        assert_eq!(out, "banned crate openssl-sys v1.0.0\n    app -> native-tls -> openssl-sys\n");
+    }
+
+    #[test]
+    fn only_via_fails_on_a_direct_dependency() {
+        let edges = [("app", "rustls"), ("rustls", "ring"), ("app", "ring")];
+        let (found, out) = check(policy("[only-via]\nring = [\"rustls\"]"), &edges);
+        assert_eq!(found, 1);
+        assert!(out.contains("app -> ring\n"), "{}", out);
+
+        let (found, _) = check(policy("[only-via]\nring = [\"rustls\"]"), &edges[..2]);
+        assert_eq!(found, 0);
+    }
+
+    #[test]
+    fn checks_ignore_the_display_options() {
+        let cfg = ConfigBuilder::new()
+                      .reduce(true)
+                      .merge_versions(true)
+                      .policy(policy("max-crates = 3\n[only-via]\nring = [\"rustls\"]"))
+                      .build();
+        let mut dg = graph(&cfg,
+                           &[("app", "rustls"), ("rustls", "ring@2.0.0"), ("app", "ring@1.0.0")]);
+        dg.normalize();
+        let mut out = vec![];
+        assert_eq!(dg.write_check_report(&mut out, &cfg.policy).unwrap(), 1);
+        assert!(String::from_utf8(out).unwrap().contains("app -> ring\n"));
+    }
+
+    #[test]
+    fn limits_depth_and_crate_count() {
+        let edges = [("app", "a"), ("a", "b"), ("b", "c")];
+        let (found, out) = check(policy("max-depth = 2"), &edges);
+        assert_eq!(found, 1);
+        assert!(out.starts_with("c v1.0.0 is 3 levels deep"), "{}", out);
+
+        assert_eq!(check(policy("max-crates = 3"), &edges).0, 0);
+        assert_eq!(check(policy("max-crates = 2"), &edges).0, 1);
+    }
+
+    #[test]
+    fn rejects_git_crates_unless_allowed() {
+        let cfg = ConfigBuilder::new().build();
+        let mut dg = graph(&cfg, &[("app", "a")]);
+        dg.nodes[1].source = Some("git+https://github.com/a/a#abc".to_owned());
+        let mut out = vec![];
+        assert_eq!(dg.write_check_report(&mut out, &policy("allow-git = false")).unwrap(), 1);
+        assert_eq!(dg.write_check_report(&mut out, &policy("allow-git = true")).unwrap(), 0);
+    }
+}
@@ -1,41 +1,61 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
 
-use toml::Value;
+use rustc_serialize::json::Json;
+use toml::{Table, Value};
 
 use dep::{DeclaredDep, DepKind};
-use graph::DepGraph;
-use error::{CliErrorKind, CliResult};
+use graph::{DepGraph, Ed};
+use error::{CliError, CliErrorKind, CliResult};
 use config::Config;
 use util;
 
 #[derive(Debug)]
-pub struct Project<'c, 'o>
-    where 'o: 'c
-{
-    cfg: &'c Config<'o>,
+pub struct Project<'c> {
+    cfg: &'c Config,
 }
 
-impl<'c, 'o> Project<'c, 'o> {
-    pub fn with_config(cfg: &'c Config<'o>) -> CliResult<Self> {
+impl<'c> Project<'c> {
+    pub fn with_config(cfg: &'c Config) -> CliResult<Self> {
         Ok(Project { cfg: cfg })
     }
 
-    pub fn graph(mut self) -> CliResult<DepGraph<'c, 'o>> {
-        let (root_deps, root_name, root_version) = try!(self.parse_root_deps());
-        let mut dg = try!(self.parse_lock_file());
+    pub fn graph(mut self) -> CliResult<DepGraph<'c>> {
+        let cfg = self.cfg;
+        let (root_deps, root_name, root_version, mut dg) = if let Some(ref file) = cfg.metadata {
+            try!(self.parse_metadata(file))
+        } else {
+            let (root_deps, root_name, root_version) = try!(self.parse_root_deps());
+            (root_deps, root_name, root_version, try!(self.parse_lock_file()))
+        };
         if !dg.set_root(&root_name, &root_version) {
             return Err(From::from(CliErrorKind::TomlNoName));
         }
+        // The lock file has no source for the root, which lives next to its manifest
+        if dg.nodes[0].source.is_none() {
+            if let Ok(manifest) = util::find_manifest_file(&cfg.manifest_file) {
+                if let Some(dir) = manifest.parent() {
+                    dg.nodes[0].source = Some(format!("path+file://{}", dir.display()));
+                }
+            }
+        }
         self.set_resolved_kind(&root_deps, &mut dg);
         if !self.cfg.include_vers {
             Project::show_version_on_duplicates(&mut dg);
         }
+        if self.cfg.reads_licenses() && self.cfg.metadata.is_none() {
+            let mut dirs = util::registry_src_dirs();
+            if let Some(ref dir) = self.cfg.vendor_dir {
+                dirs.insert(0, PathBuf::from(dir));
+            }
+            dg.find_licenses(&dirs);
+        }
         Ok(dg)
     }
 
     /// Forces the version to be displayed on dependencies
     /// that have the same name (but a different version) as another dependency.
-    fn show_version_on_duplicates(dg: &mut DepGraph<'c, 'o>) {
+    fn show_version_on_duplicates(dg: &mut DepGraph<'c>) {
         // Build a list of node IDs, sorted by the name of the dependency on that node.
         let dep_ids_sorted_by_name = {
             let mut deps = dg.nodes.iter().enumerate().collect::<Vec<_>>();
@@ -68,54 +88,104 @@ impl<'c, 'o> Project<'c, 'o> {
         }
     }
 
-    /// Sets the kind of dependency on each dependency
-    /// based on how the dependencies are declared in the manifest.
-    fn set_resolved_kind(&mut self, declared_deps: &[DeclaredDep], dg: &mut DepGraph<'c, 'o>) {
-        let declared_deps_map =
-            declared_deps.iter().map(|dd| (&*dd.name, dd.kind)).collect::<HashMap<_, _>>();
+    /// Sets the kind of each edge from how the parent declares the dependency, then the kind of
+    /// each dependency from the kinds of the edges along the paths that reach it.
+    fn set_resolved_kind(&mut self, declared_deps: &[DeclaredDep], dg: &mut DepGraph<'c>) {
+        // The manifests of path crates are found through their source, so it has to be set first
+        Project::set_path_sources(declared_deps, dg);
+        let crate_deps = self.crate_deps(dg);
 
-        dg.nodes[0].is_build = true;
-
-        dg.edges.sort(); // make sure to process edges from the root node first
-        for ed in dg.edges.iter() {
-            if ed.0 == 0 {
-                // If this is an edge from the root node,
-                // set the kind based on how the dependency is declared in the manifest file.
-                if let Some(kind) = declared_deps_map.get(&*dg.nodes[ed.1].name) {
-                    match *kind {
-                        DepKind::Build => dg.nodes[ed.1].is_build = true,
-                        DepKind::Dev => dg.nodes[ed.1].is_dev = true,
-                        DepKind::Optional => dg.nodes[ed.1].is_optional = true,
-                        _ => (),
-                    }
-                }
-            } else {
-                // If this is an edge from a dependency node, propagate the kind.
-                // This is a set of flags because a dependency can appear several times in the graph,
-                // and the kind of dependency may vary based on the path to that dependency.
-                // The flags start at false, and once they become true, they stay true.
-                // ResolvedDep::kind() will pick a kind based on their priority.
-                if dg.nodes[ed.0].is_build {
-                    dg.nodes[ed.1].is_build = true;
+        {
+            let DepGraph { ref nodes, ref mut edges, .. } = *dg;
+            for ed in edges.iter_mut() {
+                // The root's dependencies are declared in the manifest file, the others in the
+                // parent's own manifest, if it's available
+                let deps = if ed.0 == 0 {
+                    declared_deps
+                } else if let Some(ref deps) = crate_deps[ed.0] {
+                    deps
+                } else {
+                    continue;
+                };
+                let name = &nodes[ed.1].name;
+                if ed.2 == DepKind::Unk {
+                    ed.2 = Project::declared_kind(deps, name);
                 }
+                // A crate declared more than once (ex. as a normal and a dev dependency) keeps
+                // the requirement of the declaration the edge's kind comes from
+                let dd = deps.iter()
+                             .find(|dd| dd.name == *name && dd.kind == ed.2)
+                             .or_else(|| deps.iter().find(|dd| dd.name == *name));
+                ed.3 = dd.and_then(|dd| dd.req.clone());
+            }
+        }
 
-                if dg.nodes[ed.0].is_dev {
-                    dg.nodes[ed.1].is_dev = true;
+        // Walk the graph from the root, passing the kinds down along the edges.
+        // These are flags because a dependency can appear several times in the graph,
+        // and the kind of dependency may vary based on the path to that dependency.
+        // The flags start at false, and once they become true, they stay true.
+        // ResolvedDep::kind() will pick a kind based on their priority.
+        dg.nodes[0].is_build = true;
+        let mut queue = VecDeque::new();
+        queue.push_back(0);
+        while let Some(n) = queue.pop_front() {
+            let (build, dev, optional) =
+                (dg.nodes[n].is_build, dg.nodes[n].is_dev, dg.nodes[n].is_optional);
+            for &Ed(_, child, kind, _) in dg.edges.iter().filter(|e| e.0 == n) {
+                let (to_build, to_dev, to_optional) = match kind {
+                    DepKind::Build => (build, dev, optional),
+                    DepKind::Optional => (false, dev, build || optional),
+                    DepKind::Dev => (false, build || dev || optional, false),
+                    // Dependencies the root doesn't declare (ex. with an outdated lock file) are
+                    // left unclassified, deeper ones just take the kind of their parent.
+                    DepKind::Unk if n == 0 => continue,
+                    DepKind::Unk => (build, dev, optional),
+                };
+                let dep = &mut dg.nodes[child];
+                if (to_build && !dep.is_build) || (to_dev && !dep.is_dev) ||
+                   (to_optional && !dep.is_optional) {
+                    dep.is_build |= to_build;
+                    dep.is_dev |= to_dev;
+                    dep.is_optional |= to_optional;
+                    queue.push_back(child);
                 }
+            }
+        }
 
-                if dg.nodes[ed.0].is_optional {
-                    dg.nodes[ed.1].is_optional = true;
-                }
+        // A crate is only optional when every path to it goes through an optional edge, which
+        // the flags can't tell when another path is of an unknown kind
+        let mut required = vec![false; dg.nodes.len()];
+        let mut stack = vec![0];
+        while let Some(n) = stack.pop() {
+            if !required[n] {
+                required[n] = true;
+                stack.extend(dg.edges
+                               .iter()
+                               .filter(|e| e.0 == n && e.2 != DepKind::Optional)
+                               .map(|e| e.1));
+            }
+        }
+        for (dep, &required) in dg.nodes.iter_mut().zip(&required) {
+            if required {
+                dep.is_optional = false;
             }
         }
 
+        let unknown = Project::unknown_kinds(dg);
+        if !unknown.is_empty() {
+            wlnerr!("warning: unable to determine the kind of these dependencies (they may be \
+                     missing from the manifest, or the lock file may be out of date): {}",
+                    unknown.join(", "));
+        }
+
         // Remove the nodes that the user doesn't want.
         // Start at 1 to keep the root node.
         for id in (1..dg.nodes.len()).rev() {
             let kind = dg.nodes[id].kind();
             if (kind == DepKind::Build && !self.cfg.build_deps) ||
                (kind == DepKind::Dev && !self.cfg.dev_deps) ||
-               (kind == DepKind::Optional && !self.cfg.optional_deps) {
+               (kind == DepKind::Optional && !self.cfg.optional_deps) ||
+               (kind == DepKind::Unk && !self.cfg.unknown_deps) {
                 dg.remove(id);
             }
         }
@@ -123,46 +193,120 @@ impl<'c, 'o> Project<'c, 'o> {
         dg.remove_orphans();
     }
 
-    /// Builds a graph of the resolved dependencies declared in the lock file.
-    fn parse_lock_file(&mut self) -> CliResult<DepGraph<'c, 'o>> {
-        fn parse_package<'c, 'o>(dg: &mut DepGraph<'c, 'o>, pkg: &Value) {
-            let name = pkg.lookup("name")
-                          .expect("no 'name' field in Cargo.lock [package] or [root] table")
-                          .as_str()
-                          .expect("'name' field of [package] or [root] table in Cargo.lock was not a \
-                                   valid string")
-                          .to_owned();
-            let ver = pkg.lookup("version")
-                         .expect("no 'version' field in Cargo.lock [package] or [root] table")
-                         .as_str()
-                         .expect("'version' field of [package] or [root] table in Cargo.lock was not a \
-                                  valid string")
-                         .to_owned();
-
-            let id = dg.find_or_add(&*name, &*ver);
+    /// Lists the crates of an unknown kind the root reaches (ex. `log v0.4.8`). Lock file
+    /// packages the root can't reach are dropped anyway, so they aren't worth a warning.
+    fn unknown_kinds(dg: &DepGraph<'c>) -> Vec<String> {
+        let mut reachable = vec![false; dg.nodes.len()];
+        let mut stack = vec![0];
+        while let Some(n) = stack.pop() {
+            if !reachable[n] {
+                reachable[n] = true;
+                stack.extend(dg.edges.iter().filter(|e| e.0 == n).map(|e| e.1));
+            }
+        }
+        dg.nodes
+          .iter()
+          .enumerate()
+          .filter(|&(id, dep)| reachable[id] && dep.kind() == DepKind::Unk)
+          .map(|(_, dep)| format!("{} v{}", dep.name, dep.ver))
+          .collect()
+    }
 
-            if let Some(&Value::Array(ref deps)) = pkg.lookup("dependencies") {
-                for dep in deps {
-                    let dep_vec = dep.as_str().unwrap_or("").split(' ').collect::<Vec<_>>();
-                    let dep_string = dep_vec[0].to_owned();
-                    let ver = dep_vec[1];
-                    dg.add_child(id, &*dep_string, ver);
+    /// Sets the source of path crates, which the lock file leaves out, to `path+file://<dir>`.
+    /// The paths of the root's path dependencies come from its manifest, and the paths of
+    /// their own path dependencies from theirs.
+    fn set_path_sources(root_deps: &[DeclaredDep], dg: &mut DepGraph<'c>) {
+        fn paths(deps: &[DeclaredDep]) -> Vec<(String, String)> {
+            deps.iter()
+                .filter_map(|dd| dd.path.as_ref().map(|p| (dd.name.clone(), p.clone())))
+                .collect()
+        }
+
+        let mut queue = VecDeque::new();
+        queue.push_back((0, paths(root_deps)));
+        while let Some((parent, deps)) = queue.pop_front() {
+            for (name, path) in deps {
+                let children = dg.edges
+                                 .iter()
+                                 .filter(|e| e.0 == parent && dg.nodes[e.1].name == name)
+                                 .map(|e| e.1)
+                                 .collect::<Vec<_>>();
+                for child in children {
+                    if dg.nodes[child].source.is_some() {
+                        continue;
+                    }
+                    debugln!("set_path_sources; crate={} path={}", name, path);
+                    dg.nodes[child].source = Some(format!("path+file://{}", path));
+                    let dir = Path::new(&path);
+                    if let Ok(toml) = util::toml_from_file(dir.join("Cargo.toml")) {
+                        queue.push_back((child, paths(&Project::declared_deps(&toml, dir))));
+                    }
                 }
             }
         }
+    }
+
+    /// Builds a graph of the resolved dependencies declared in the lock file.
+    fn parse_lock_file(&mut self) -> CliResult<DepGraph<'c>> {
+        fn invalid(what: String) -> CliError {
+            From::from(CliErrorKind::Generic(format!("invalid lock file: {}", what)))
+        }
+        fn name_and_ver(pkg: &Value) -> CliResult<(&str, &str)> {
+            match (pkg.lookup("name").and_then(Value::as_str),
+                   pkg.lookup("version").and_then(Value::as_str)) {
+                (Some(n), Some(v)) => Ok((n, v)),
+                _ => Err(invalid("a [package] or [root] table has no name or version".to_owned())),
+            }
+        }
 
-        let lock_path = try!(util::find_manifest_file(self.cfg.lock_file));
+        let lock_path = try!(util::find_manifest_file(&self.cfg.lock_file));
         let lock_toml = try!(util::toml_from_file(lock_path));
 
-        let mut dg = DepGraph::new(self.cfg);
-
+        let mut packages = vec![];
         if let Some(root) = lock_toml.get("root") {
-            parse_package(&mut dg, root);
+            packages.push(root);
+        }
+        if let Some(&Value::Array(ref pkgs)) = lock_toml.get("package") {
+            packages.extend(pkgs.iter());
         }
 
-        if let Some(&Value::Array(ref packages)) = lock_toml.get("package") {
-            for pkg in packages {
-                parse_package(&mut dg, pkg);
+        // Newer lock files leave out the version of a dependency when a single version of that
+        // crate is locked
+        let mut versions = HashMap::new();
+        for pkg in &packages {
+            let (name, ver) = try!(name_and_ver(pkg));
+            versions.entry(name).or_insert_with(Vec::new).push(ver);
+        }
+
+        let mut dg = DepGraph::new(self.cfg);
+        for pkg in &packages {
+            let (name, ver) = try!(name_and_ver(pkg));
+            let id = dg.find_or_add(name, ver);
+            if let Some(source) = pkg.lookup("source").and_then(Value::as_str) {
+                dg.nodes[id].source = Some(source.to_owned());
+            }
+
+            if let Some(&Value::Array(ref deps)) = pkg.lookup("dependencies") {
+                for dep in deps {
+                    // `name`, `name version`, or `name version (source)`
+                    let mut parts = dep.as_str().unwrap_or("").split(' ');
+                    let dep_name = parts.next().unwrap_or("");
+                    let dep_ver = match parts.next() {
+                        Some(ver) => ver,
+                        None => {
+                            match versions.get(dep_name) {
+                                Some(vers) if vers.len() == 1 => vers[0],
+                                _ => {
+                                    return Err(invalid(format!("unable to tell which version of \
+                                                                `{}` `{}` depends on",
+                                                               dep_name,
+                                                               name)))
+                                }
+                            }
+                        }
+                    };
+                    dg.add_child(id, dep_name, dep_ver, DepKind::Unk);
+                }
             }
         }
 
@@ -171,14 +315,139 @@ impl<'c, 'o> Project<'c, 'o> {
         Ok(dg)
     }
 
+    /// Builds a graph of the resolved dependencies, along with the dependencies declared by the
+    /// root package, from the output of `cargo metadata --format-version 1`.
+    fn parse_metadata(&mut self,
+                      file: &str)
+                      -> CliResult<(Vec<DeclaredDep>, String, String, DepGraph<'c>)> {
+        fn invalid(what: &str) -> CliError {
+            From::from(CliErrorKind::Generic(format!("invalid `cargo metadata` output: {}", what)))
+        }
+        fn name_and_ver(pkg: &Json) -> Option<(&str, &str)> {
+            match (pkg.find("name").and_then(Json::as_string),
+                   pkg.find("version").and_then(Json::as_string)) {
+                (Some(n), Some(v)) => Some((n, v)),
+                _ => None,
+            }
+        }
+
+        debugln!("executing; parse_metadata; file={:?}", file);
+        let meta = try!(util::json_from_file(file));
+
+        let mut packages = HashMap::new();
+        if let Some(pkgs) = meta.find("packages").and_then(Json::as_array) {
+            for pkg in pkgs {
+                if let Some(id) = pkg.find("id").and_then(Json::as_string) {
+                    packages.insert(id, pkg);
+                }
+            }
+        } else {
+            return Err(invalid("no 'packages' array"));
+        }
+
+        let resolve = try!(meta.find("resolve")
+                               .and_then(Json::as_object)
+                               .ok_or_else(|| invalid("no 'resolve' table (was --no-deps used?)")));
+
+        let mut dg = DepGraph::new(self.cfg);
+
+        if let Some(nodes) = resolve.get("nodes").and_then(Json::as_array) {
+            for node in nodes {
+                let node_id = try!(node.find("id")
+                                       .and_then(Json::as_string)
+                                       .ok_or_else(|| invalid("no 'id' for node in 'resolve'")));
+                let (name, ver) = try!(packages.get(node_id)
+                                           .and_then(|pkg| name_and_ver(pkg))
+                                           .ok_or_else(|| invalid("unknown package in 'resolve'")));
+                let id = dg.find_or_add(name, ver);
+                dg.nodes[id].source = Project::metadata_source(packages[node_id]);
+                dg.nodes[id].license = packages[node_id]
+                                           .find("license")
+                                           .and_then(Json::as_string)
+                                           .map(ToOwned::to_owned);
+
+                let declared = Project::metadata_deps(packages[node_id]);
+                let unknown_dep = || invalid("unknown dependency in 'resolve'");
+                if let Some(deps) = node.find("deps").and_then(Json::as_array) {
+                    // Each dependency comes with the kinds it's resolved as, which also cover
+                    // build and target-specific dependencies
+                    for dep in deps {
+                        let (name, ver) = try!(dep.find("pkg")
+                                                  .and_then(Json::as_string)
+                                                  .and_then(|id| packages.get(id))
+                                                  .and_then(|pkg| name_and_ver(pkg))
+                                                  .ok_or_else(&unknown_dep));
+                        let kind = dep.find("dep_kinds")
+                                      .and_then(Json::as_array)
+                                      .into_iter()
+                                      .flat_map(|kinds| kinds)
+                                      .map(|k| {
+                                          let kind = k.find("kind").and_then(Json::as_string);
+                                          Project::metadata_kind(kind, &declared, name)
+                                      })
+                                      .max_by_key(DepKind::priority)
+                                      .unwrap_or_else(|| Project::declared_kind(&declared, name));
+                        dg.add_child(id, name, ver, kind);
+                    }
+                } else if let Some(deps) = node.find("dependencies").and_then(Json::as_array) {
+                    // Older versions of cargo only list the dependencies
+                    for dep in deps {
+                        let (name, ver) = try!(dep.as_string()
+                                                  .and_then(|id| packages.get(id))
+                                                  .and_then(|pkg| name_and_ver(pkg))
+                                                  .ok_or_else(&unknown_dep));
+                        dg.add_child(id, name, ver, Project::declared_kind(&declared, name));
+                    }
+                }
+            }
+        }
+
+        if let Some(root) = resolve.get("root").and_then(Json::as_string) {
+            let root = try!(packages.get(root)
+                                    .ok_or_else(|| invalid("unknown root package")));
+            let (root_name, root_version) = try!(name_and_ver(root)
+                                                     .ok_or(CliError::from(CliErrorKind::TomlNoName)));
+            let declared_deps = Project::metadata_deps(root);
+            debugln!("return=parse_metadata; declared_deps={:#?}", declared_deps);
+            debugln!("return=parse_metadata; dg={:#?}", dg);
+            return Ok((declared_deps, root_name.to_owned(), root_version.to_owned(), dg));
+        }
+
+        // A virtual workspace has no root package, so the root is the workspace itself, named
+        // after its directory and depending on every member
+        let members = try!(meta.find("workspace_members")
+                               .and_then(Json::as_array)
+                               .ok_or_else(|| invalid("no root package or workspace members")));
+        let ws_root = meta.find("workspace_root").and_then(Json::as_string).unwrap_or("");
+        let root_name = Path::new(ws_root)
+                            .file_name()
+                            .and_then(|n| n.to_str())
+                            .unwrap_or("workspace")
+                            .to_owned();
+        let root_version = "0.0.0".to_owned();
+        let root_id = dg.find_or_add(&root_name, &root_version);
+        dg.nodes[root_id].source = Some(format!("path+file://{}", ws_root));
+        let mut declared_deps = vec![];
+        for member in members {
+            let pkg = try!(member.as_string()
+                                 .and_then(|id| packages.get(id))
+                                 .ok_or_else(|| invalid("unknown workspace member")));
+            let (name, ver) = try!(name_and_ver(pkg).ok_or_else(|| invalid("unnamed member")));
+            dg.add_child(root_id, name, ver, DepKind::Build);
+            declared_deps.push(DeclaredDep::with_kind(name.to_owned(), DepKind::Build));
+        }
+
+        debugln!("return=parse_metadata; declared_deps={:#?}", declared_deps);
+        debugln!("return=parse_metadata; dg={:#?}", dg);
+        Ok((declared_deps, root_name.to_owned(), root_version.to_owned(), dg))
+    }
+
     /// Builds a list of the dependencies declared in the manifest file.
     pub fn parse_root_deps(&mut self) -> CliResult<(Vec<DeclaredDep>, String, String)> {
         debugln!("executing; parse_root_deps;");
-        let manifest_path = try!(util::find_manifest_file(self.cfg.manifest_file));
-        let manifest_toml = try!(util::toml_from_file(manifest_path));
-
-        let mut declared_deps = vec![];
-        let mut v = vec![];
+        let manifest_path = try!(util::find_manifest_file(&self.cfg.manifest_file));
+        let manifest_toml = try!(util::toml_from_file(&manifest_path));
+        let manifest_dir = manifest_path.parent().unwrap_or(Path::new(""));
 
         let (root_name, root_version) = {
             let mut name = None;
@@ -200,31 +469,560 @@ impl<'c, 'o> Project<'c, 'o> {
             }
         };
 
-        if let Some(table) = manifest_toml.get("dependencies") {
-            if let Some(table) = table.as_table() {
-                for (name, dep_table) in table.into_iter() {
-                    if let Some(&Value::Boolean(true)) = dep_table.lookup("optional") {
-                        declared_deps.push(DeclaredDep::with_kind(name.clone(), DepKind::Optional));
-                    } else {
-                        declared_deps.push(DeclaredDep::with_kind(name.clone(), DepKind::Build));
-                    }
-                    v.push(name.clone());
+        let declared_deps = Project::declared_deps(&manifest_toml, manifest_dir);
+
+        debugln!("return=parse_root_deps; self={:#?}", self);
+        debugln!("return=parse_root_deps; declared_deps={:#?}", declared_deps);
+        debugln!("return=parse_root_deps; root_name={:#?}", root_name);
+        Ok((declared_deps, root_name, root_version))
+    }
+
+    /// Builds a list of the dependencies declared in a manifest. `manifest_dir` is used to
+    /// resolve the paths of path dependencies.
+    fn declared_deps(manifest_toml: &Table, manifest_dir: &Path) -> Vec<DeclaredDep> {
+        let path_of = |dep: &Value| {
+            dep.lookup("path")
+               .and_then(Value::as_str)
+               .map(|p| manifest_dir.join(p).display().to_string())
+        };
+        // A renamed dependency names the real package in `package`
+        let package_of = |name: &String, dep: &Value| {
+            dep.lookup("package")
+               .and_then(Value::as_str)
+               .map_or_else(|| name.clone(), ToOwned::to_owned)
+        };
+
+        // Target-specific dependencies (`[target.'cfg(unix)'.dependencies]`) are read like the
+        // others, whichever target they're for
+        let mut tables = vec![manifest_toml];
+        if let Some(&Value::Table(ref targets)) = manifest_toml.get("target") {
+            tables.extend(targets.values().filter_map(Value::as_table));
+        }
+
+        let mut declared_deps = vec![];
+        for table in tables {
+            for &(key, kind) in &[("dependencies", DepKind::Build),
+                                  // build-dependencies are compiled like normal ones, so they're
+                                  // drawn the same way
+                                  ("build-dependencies", DepKind::Build),
+                                  ("dev-dependencies", DepKind::Dev)] {
+                let deps = match table.get(key).and_then(Value::as_table) {
+                    Some(deps) => deps,
+                    None => continue,
+                };
+                for (name, dep_table) in deps {
+                    let kind = match dep_table.lookup("optional") {
+                        Some(&Value::Boolean(true)) if kind == DepKind::Build => DepKind::Optional,
+                        _ => kind,
+                    };
+                    declared_deps.push(DeclaredDep::with_kind(package_of(name, dep_table), kind)
+                                           .with_req(Project::requirement(dep_table,
+                                                                          manifest_dir))
+                                           .with_path(path_of(dep_table)));
                 }
             }
         }
 
-        if let Some(table) = manifest_toml.get("dev-dependencies") {
-            if let Some(table) = table.as_table() {
-                for (name, _) in table.into_iter() {
-                    declared_deps.push(DeclaredDep::with_kind(name.clone(), DepKind::Dev));
-                    v.push(name.clone());
-                }
+        declared_deps
+    }
+
+    /// Returns the kind a dependency is declared with in `deps`. When it's declared more than
+    /// once, the kind with the highest priority wins.
+    fn declared_kind(deps: &[DeclaredDep], name: &str) -> DepKind {
+        deps.iter()
+            .filter(|dd| dd.name == name)
+            .map(|dd| dd.kind)
+            .max_by_key(DepKind::priority)
+            .unwrap_or(DepKind::Unk)
+    }
+
+    /// Reads the dependencies each crate declares in its own manifest, when the manifest is
+    /// available locally: in the crate's path, the vendor directory, or with
+    /// `--registry-sources`, the registry sources. The result is indexed by node.
+    fn crate_deps(&self, dg: &DepGraph<'c>) -> Vec<Option<Vec<DeclaredDep>>> {
+        let mut dirs = vec![];
+        if let Some(ref dir) = self.cfg.vendor_dir {
+            dirs.push(PathBuf::from(dir));
+        }
+        if self.cfg.registry_sources {
+            dirs.extend(util::registry_src_dirs());
+        }
+        dg.nodes
+          .iter()
+          .map(|dep| {
+              let manifest = match dep.source {
+                  Some(ref src) if src.starts_with("path+file://") => {
+                      Some(Path::new(&src["path+file://".len()..]).join("Cargo.toml"))
+                  }
+                  _ => util::find_crate_manifest(&dirs, &dep.name, &dep.ver),
+              };
+              manifest.and_then(|manifest| {
+                  let toml = util::toml_from_file(&manifest).ok();
+                  toml.map(|t| {
+                      Project::declared_deps(&t, manifest.parent().unwrap_or(Path::new("")))
+                  })
+              })
+          })
+          .collect()
+    }
+
+    /// Returns the kind of a dependency from one of its `dep_kinds` in `cargo metadata` output,
+    /// where a normal dependency is optional if the package declares it so.
+    fn metadata_kind(kind: Option<&str>, declared: &[DeclaredDep], name: &str) -> DepKind {
+        match kind {
+            Some("dev") => DepKind::Dev,
+            // build-dependencies are compiled like normal ones, so they're drawn the same way
+            Some("build") => DepKind::Build,
+            Some(_) => DepKind::Unk,
+            None if declared.iter().any(|dd| dd.name == name && dd.kind == DepKind::Optional) => {
+                DepKind::Optional
             }
+            None => DepKind::Build,
         }
+    }
 
-        debugln!("return=parse_root_deps; self={:#?}", self);
-        debugln!("return=parse_root_deps; declared_deps={:#?}", declared_deps);
-        debugln!("return=parse_root_deps; root_name={:#?}", root_name);
-        Ok((declared_deps, root_name, root_version))
+    /// Returns the dependencies a package declares in `cargo metadata` output.
+    fn metadata_deps(pkg: &Json) -> Vec<DeclaredDep> {
+        // `name` is always the real package name, even when the dependency was renamed.
+        let mut declared_deps = vec![];
+        if let Some(deps) = pkg.find("dependencies").and_then(Json::as_array) {
+            for dep in deps {
+                let name = if let Some(n) = dep.find("name").and_then(Json::as_string) {
+                    n.to_owned()
+                } else {
+                    continue;
+                };
+                let optional = dep.find("optional").and_then(Json::as_boolean).unwrap_or(false);
+                let kind = match dep.find("kind").and_then(Json::as_string) {
+                    Some("dev") => DepKind::Dev,
+                    // Like in the manifest, build-dependencies are drawn like normal ones
+                    Some("build") => DepKind::Build,
+                    Some(_) => continue,
+                    None if optional => DepKind::Optional,
+                    None => DepKind::Build,
+                };
+                // Same precedence as `requirement`, where `*` means no version was given
+                let req = match dep.find("req").and_then(Json::as_string) {
+                    Some(req) if req != "*" => Some(req.to_owned()),
+                    req => {
+                        match dep.find("source").and_then(Json::as_string) {
+                            Some(src) if src.starts_with("git+") => {
+                                Some(Project::metadata_git_requirement(&src[4..]))
+                            }
+                            _ => {
+                                // Written relative to the package, as in its manifest
+                                let dir = pkg.find("manifest_path")
+                                             .and_then(Json::as_string)
+                                             .and_then(|p| Path::new(p).parent());
+                                match (dep.find("path").and_then(Json::as_string), dir) {
+                                    (Some(path), Some(dir)) => {
+                                        Some(util::relative_path(Path::new(path), dir))
+                                    }
+                                    (path, _) => path.or(req).map(ToOwned::to_owned),
+                                }
+                            }
+                        }
+                    }
+                };
+                declared_deps.push(DeclaredDep::with_kind(name, kind).with_req(req));
+            }
+        }
+
+        declared_deps
+    }
+
+    /// Returns the source of a package in `cargo metadata` output, using the same format as the
+    /// lock file. Path packages, which have no source there, become `path+file://<dir>`.
+    fn metadata_source(pkg: &Json) -> Option<String> {
+        if let Some(source) = pkg.find("source").and_then(Json::as_string) {
+            return Some(source.to_owned());
+        }
+        pkg.find("manifest_path")
+           .and_then(Json::as_string)
+           .and_then(|p| Path::new(p).parent())
+           .map(|dir| format!("path+file://{}", dir.display()))
+    }
+
+    /// Turns the source of a git dependency in `cargo metadata` output (ex.
+    /// `https://github.com/serde-rs/serde?tag=v1.0.0`) into the requirement the manifest would
+    /// give for it.
+    fn metadata_git_requirement(source: &str) -> String {
+        let source = source.split('#').next().unwrap_or(source);
+        let mut parts = source.splitn(2, '?');
+        let repo = parts.next().unwrap_or(source);
+        let rev = parts.next()
+                       .into_iter()
+                       .flat_map(|query| query.split('&'))
+                       .filter_map(|param| {
+                           let mut kv = param.splitn(2, '=');
+                           match (kv.next(), kv.next()) {
+                               (Some("rev"), rev) | (Some("tag"), rev) | (Some("branch"), rev) => {
+                                   rev
+                               }
+                               _ => None,
+                           }
+                       })
+                       .next();
+        Project::git_requirement(repo, rev)
+    }
+
+    /// The requirement of a git dependency: its repository, and the revision if one was given
+    /// (ex. `https://github.com/serde-rs/serde#v1.0.0`).
+    fn git_requirement(repo: &str, rev: Option<&str>) -> String {
+        match rev {
+            Some(rev) => format!("{}#{}", repo, rev),
+            None => repo.to_owned(),
+        }
+    }
+
+    /// Returns how a dependency was declared in the manifest: its version requirement, or for
+    /// git and path dependencies without one, the repository (and revision) or path. These are
+    /// written the way `cargo metadata` gives them, so both inputs label edges the same way.
+    fn requirement(dep: &Value, manifest_dir: &Path) -> Option<String> {
+        let table = match *dep {
+            Value::String(ref req) => return Some(Project::normalize_req(req)),
+            Value::Table(ref table) => table,
+            _ => return None,
+        };
+        if let Some(&Value::String(ref req)) = table.get("version") {
+            return Some(Project::normalize_req(req));
+        }
+        if let Some(&Value::String(ref git)) = table.get("git") {
+            let rev = ["rev", "tag", "branch"].iter().filter_map(|key| table.get(*key)).next();
+            return Some(Project::git_requirement(git, rev.and_then(Value::as_str)));
+        }
+        if let Some(&Value::String(ref path)) = table.get("path") {
+            return Some(util::relative_path(&manifest_dir.join(path), manifest_dir));
+        }
+        None
+    }
+
+    /// Writes a version requirement the way cargo does, where a bare version is a caret
+    /// requirement (ex. `1.2` is `^1.2`, and `>= 1, < 2` is `>=1, <2`).
+    fn normalize_req(req: &str) -> String {
+        req.split(',')
+           .map(|part| {
+               let part = part.trim();
+               let ver_start = part.find(|c: char| !"<>=~^ ".contains(c)).unwrap_or(part.len());
+               let (op, ver) = (part[..ver_start].trim(), &part[ver_start..]);
+               if op.is_empty() && !ver.contains('*') {
+                   format!("^{}", ver)
+               } else {
+                   format!("{}{}", op, ver)
+               }
+           })
+           .collect::<Vec<_>>()
+           .join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Write;
+    use std::path::Path;
+
+    use rustc_serialize::json::Json;
+    use toml::Value;
+
+    use config::ConfigBuilder;
+    use dep::{DeclaredDep, DepKind};
+    use graph::tests::{edges, graph};
+    use super::Project;
+
+    /// Writes a fixture to the temporary directory, returning its path.
+    fn temp_file(name: &str, contents: &str) -> String {
+        let path = env::temp_dir().join(format!("cargo-graph-{}", name));
+        File::create(&path).unwrap().write_all(contents.as_bytes()).unwrap();
+        path.display().to_string()
+    }
+
+    /// Writes the manifest of `app`, which depends on `core` by path, in a temporary
+    /// workspace, returning the path of the manifest.
+    fn app_manifest(name: &str, deps: &str) -> String {
+        let ws = env::temp_dir().join(format!("cargo-graph-{}", name));
+        for dir in &["app", "core"] {
+            fs::create_dir_all(ws.join(dir)).unwrap();
+        }
+        File::create(ws.join("core/Cargo.toml"))
+            .unwrap()
+            .write_all(b"[package]\nname = \"core\"\nversion = \"0.1.0\"\n")
+            .unwrap();
+        let manifest = ws.join("app/Cargo.toml");
+        File::create(&manifest)
+            .unwrap()
+            .write_all(format!("[package]\nname = \"app\"\nversion = \"0.1.0\"\n{}", deps)
+                           .as_bytes())
+            .unwrap();
+        manifest.display().to_string()
+    }
+
+    fn reqs(deps: &[DeclaredDep]) -> Vec<(String, DepKind, Option<String>)> {
+        let mut reqs = deps.iter()
+                           .map(|dd| (dd.name.clone(), dd.kind, dd.req.clone()))
+                           .collect::<Vec<_>>();
+        reqs.sort();
+        reqs
+    }
+
+    #[test]
+    fn manifest_and_metadata_agree_on_requirements() {
+        let manifest = app_manifest("reqs",
+                                    r#"
+            [dependencies]
+            a = "1.0"
+            b = { version = ">= 0.3, < 0.5", path = "../b" }
+            c = { git = "https://github.com/x/c", tag = "v1" }
+            d = { git = "https://github.com/x/d" }
+            core = { path = "../core/" }
+            f = { version = "2", git = "https://github.com/x/f" }
+        "#);
+        let cfg = ConfigBuilder::new().manifest_file(&*manifest).build();
+        let (from_manifest, _, _) = Project::with_config(&cfg).unwrap().parse_root_deps().unwrap();
+
+        // What `cargo metadata` gives for the same manifest
+        let app_dir = Path::new(&manifest).parent().unwrap();
+        let core_dir = app_dir.parent().unwrap().join("core");
+        let pkg = Json::from_str(&format!(r#"{{"manifest_path": {:?}, "dependencies": [
+            {{"name": "a", "req": "^1.0", "source": "registry+https://example.com/index"}},
+            {{"name": "b", "req": ">=0.3, <0.5", "path": "/elsewhere/b"}},
+            {{"name": "c", "req": "*", "source": "git+https://github.com/x/c?tag=v1"}},
+            {{"name": "d", "req": "*", "source": "git+https://github.com/x/d"}},
+            {{"name": "core", "req": "*", "path": {:?}}},
+            {{"name": "f", "req": "^2", "source": "git+https://github.com/x/f"}}
+        ]}}"#,
+                                          manifest,
+                                          core_dir.display().to_string()))
+                      .unwrap();
+        let from_metadata = Project::metadata_deps(&pkg);
+
+        let req = |r: &str| Some(r.to_owned());
+        let expected = vec![("a".to_owned(), DepKind::Build, req("^1.0")),
+                            ("b".to_owned(), DepKind::Build, req(">=0.3, <0.5")),
+                            ("c".to_owned(), DepKind::Build, req("https://github.com/x/c#v1")),
+                            ("core".to_owned(), DepKind::Build, req("../core")),
+                            ("d".to_owned(), DepKind::Build, req("https://github.com/x/d")),
+                            ("f".to_owned(), DepKind::Build, req("^2"))];
+        assert_eq!(reqs(&from_manifest), expected);
+        assert_eq!(reqs(&from_metadata), expected);
+        assert_eq!(Project::requirement(&Value::Integer(1), app_dir), None);
+    }
+
+    #[test]
+    fn requirements_are_kept_on_the_edge_of_the_matching_kind() {
+        let manifest = app_manifest("edge-reqs",
+                                    r#"
+            [dependencies]
+            log = "0.4"
+
+            [dev-dependencies]
+            log = { version = "0.4.8", features = ["std"] }
+        "#);
+        let cfg = ConfigBuilder::new().manifest_file(&*manifest).build();
+        let mut project = Project::with_config(&cfg).unwrap();
+        let (declared, _, _) = project.parse_root_deps().unwrap();
+        let mut dg = graph(&cfg, &[("app@0.1.0", "log@0.4.8")]);
+        dg.edges[0].2 = DepKind::Unk;
+        project.set_resolved_kind(&declared, &mut dg);
+        assert_eq!(dg.edges[0].2, DepKind::Build);
+        assert_eq!(dg.edges[0].3, Some("^0.4".to_owned()));
+    }
+
+    #[test]
+    fn lock_file_entries_without_a_version_use_the_only_locked_one() {
+        let lock = temp_file("one-field.lock",
+                             r#"
+            [[package]]
+            name = "app"
+            version = "0.1.0"
+            dependencies = ["log", "serde 1.0.100 (registry+https://example.com/index)"]
+
+            [[package]]
+            name = "log"
+            version = "0.4.8"
+            source = "registry+https://example.com/index"
+
+            [[package]]
+            name = "serde"
+            version = "1.0.100"
+            source = "registry+https://example.com/index"
+        "#);
+        let cfg = ConfigBuilder::new().lock_file(lock).build();
+        let dg = Project::with_config(&cfg).unwrap().parse_lock_file().unwrap();
+        assert_eq!(edges(&dg), ["app -> log", "app -> serde"]);
+        assert_eq!(dg.nodes[dg.find("log", "0.4.8").unwrap()].source,
+                   Some("registry+https://example.com/index".to_owned()));
+    }
+
+    #[test]
+    fn lock_file_entries_with_an_ambiguous_version_are_an_error() {
+        let lock = temp_file("ambiguous.lock",
+                             r#"
+            [[package]]
+            name = "app"
+            version = "0.1.0"
+            dependencies = ["log"]
+
+            [[package]]
+            name = "log"
+            version = "0.3.9"
+
+            [[package]]
+            name = "log"
+            version = "0.4.8"
+        "#);
+        let cfg = ConfigBuilder::new().lock_file(lock).build();
+        assert!(Project::with_config(&cfg).unwrap().parse_lock_file().is_err());
+    }
+
+    #[test]
+    fn metadata_kinds_come_from_dep_kinds_and_virtual_workspaces_keep_every_member() {
+        let meta = temp_file("virtual.json",
+                             r#"{
+            "packages": [
+                {"id": "a 0.1.0", "name": "a", "version": "0.1.0",
+                 "manifest_path": "/ws/a/Cargo.toml",
+                 "dependencies": [{"name": "b", "req": "^0.1", "kind": null, "optional": false},
+                                  {"name": "c", "req": "*", "kind": "build", "optional": false},
+                                  {"name": "d", "req": "*", "kind": "dev", "optional": false},
+                                  {"name": "e", "req": "*", "kind": null, "optional": true}]},
+                {"id": "b 0.1.0", "name": "b", "version": "0.1.0",
+                 "manifest_path": "/ws/b/Cargo.toml", "dependencies": []},
+                {"id": "c 0.1.0", "name": "c", "version": "0.1.0",
+                 "source": "registry+https://example.com/index", "dependencies": []},
+                {"id": "d 0.1.0", "name": "d", "version": "0.1.0",
+                 "source": "registry+https://example.com/index", "dependencies": []},
+                {"id": "e 0.1.0", "name": "e", "version": "0.1.0",
+                 "source": "registry+https://example.com/index", "dependencies": []}
+            ],
+            "workspace_members": ["a 0.1.0", "b 0.1.0"],
+            "workspace_root": "/ws",
+            "resolve": {
+                "root": null,
+                "nodes": [
+                    {"id": "a 0.1.0", "deps": [
+                        {"name": "b", "pkg": "b 0.1.0", "dep_kinds": [{"kind": null}]},
+                        {"name": "c", "pkg": "c 0.1.0", "dep_kinds": [{"kind": "build"}]},
+                        {"name": "d", "pkg": "d 0.1.0", "dep_kinds": [{"kind": "dev"}]},
+                        {"name": "e", "pkg": "e 0.1.0", "dep_kinds": [{"kind": null}]}
+                    ]},
+                    {"id": "b 0.1.0", "deps": []},
+                    {"id": "c 0.1.0", "deps": []},
+                    {"id": "d 0.1.0", "deps": []},
+                    {"id": "e 0.1.0", "deps": []}
+                ]
+            }
+        }"#);
+        let cfg = ConfigBuilder::new().build();
+        let (declared, name, ver, dg) = Project::with_config(&cfg)
+                                            .unwrap()
+                                            .parse_metadata(&meta)
+                                            .unwrap();
+        assert_eq!((&*name, &*ver), ("ws", "0.0.0"));
+        assert_eq!(declared.iter().map(|dd| &*dd.name).collect::<Vec<_>>(), ["a", "b"]);
+        assert_eq!(edges(&dg), ["a -> b", "a -> c", "a -> d", "a -> e", "ws -> a", "ws -> b"]);
+        let kinds = dg.edges
+                      .iter()
+                      .filter(|e| dg.nodes[e.0].name == "a")
+                      .map(|e| (&*dg.nodes[e.1].name, e.2))
+                      .collect::<Vec<_>>();
+        assert_eq!(kinds,
+                   [("b", DepKind::Build), ("c", DepKind::Build), ("d", DepKind::Dev),
+                    ("e", DepKind::Optional)]);
+    }
+
+    #[test]
+    fn vendored_manifests_give_the_kinds_of_transitive_edges() {
+        let vendor = env::temp_dir().join("cargo-graph-vendor");
+        fs::create_dir_all(vendor.join("serde")).unwrap();
+        File::create(vendor.join("serde/Cargo.toml"))
+            .unwrap()
+            .write_all(br#"
+                [package]
+                name = "serde"
+                version = "1.0.0"
+
+                [dependencies]
+                serde_derive = { version = "1.0", optional = true }
+
+                [dev-dependencies]
+                serde_test = "1.0"
+            "#)
+            .unwrap();
+        let cfg = ConfigBuilder::new().vendor_dir(vendor.to_str().unwrap()).build();
+        let mut dg = graph(&cfg,
+                           &[("app", "serde"), ("serde", "serde_derive"), ("serde", "serde_test")]);
+        for ed in &mut dg.edges {
+            ed.2 = DepKind::Unk;
+        }
+        let declared = vec![DeclaredDep::with_kind("serde".to_owned(), DepKind::Build)];
+        Project::with_config(&cfg).unwrap().set_resolved_kind(&declared, &mut dg);
+        let kinds = dg.edges
+                      .iter()
+                      .map(|e| (&*dg.nodes[e.1].name, e.2))
+                      .collect::<Vec<_>>();
+        assert_eq!(kinds,
+                   [("serde", DepKind::Build), ("serde_derive", DepKind::Optional),
+                    ("serde_test", DepKind::Dev)]);
+    }
+
+    #[test]
+    fn crates_are_optional_only_when_every_path_goes_through_an_optional_edge() {
+        let cfg = ConfigBuilder::new().build();
+        let mut dg = graph(&cfg,
+                           &[("app", "opt"), ("app", "log"), ("app", "winapi"), ("opt", "log"),
+                             ("opt", "libc"), ("opt", "winapi")]);
+        for dep in &mut dg.nodes {
+            dep.is_build = false;
+        }
+        for ed in &mut dg.edges {
+            if ed.0 == 0 {
+                ed.2 = DepKind::Unk;
+            }
+        }
+        // The root doesn't declare `winapi`, as if the lock file were out of date
+        let declared = vec![DeclaredDep::with_kind("opt".to_owned(), DepKind::Optional),
+                            DeclaredDep::with_kind("log".to_owned(), DepKind::Build)];
+        Project::with_config(&cfg).unwrap().set_resolved_kind(&declared, &mut dg);
+        let kinds = dg.nodes
+                      .iter()
+                      .map(|dep| (&*dep.name, dep.kind()))
+                      .collect::<Vec<_>>();
+        assert_eq!(kinds,
+                   [("app", DepKind::Build), ("opt", DepKind::Optional), ("log", DepKind::Build),
+                    ("winapi", DepKind::Unk), ("libc", DepKind::Optional)]);
+    }
+
+    #[test]
+    fn build_and_target_specific_dependencies_are_declared() {
+        let manifest = app_manifest("build-deps",
+                                    r#"
+            [build-dependencies]
+            cc = "1.0"
+
+            [target.'cfg(unix)'.dependencies]
+            libc = { version = "0.2", optional = true }
+
+            [target.'cfg(windows)'.dev-dependencies]
+            winapi = "0.3"
+        "#);
+        let cfg = ConfigBuilder::new().manifest_file(&*manifest).build();
+        let (declared, _, _) = Project::with_config(&cfg).unwrap().parse_root_deps().unwrap();
+        let req = |r: &str| Some(r.to_owned());
+        assert_eq!(reqs(&declared),
+                   [("cc".to_owned(), DepKind::Build, req("^1.0")),
+                    ("libc".to_owned(), DepKind::Optional, req("^0.2")),
+                    ("winapi".to_owned(), DepKind::Dev, req("^0.3"))]);
+    }
+
+    #[test]
+    fn only_reachable_crates_of_an_unknown_kind_are_listed() {
+        let cfg = ConfigBuilder::new().build();
+        let mut dg = graph(&cfg, &[("app", "a"), ("app", "b@0.1.0"), ("stale", "c")]);
+        for dep in &mut dg.nodes {
+            dep.is_build = dep.name == "app" || dep.name == "a";
+        }
+        assert_eq!(Project::unknown_kinds(&dg), ["b v0.1.0"]);
     }
 }
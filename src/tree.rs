@@ -0,0 +1,92 @@
+use std::io::{self, Write};
+
+use dep::DepKind;
+use error::CliResult;
+use fmt::Format;
+use graph::{DepGraph, Nd};
+
+impl<'c> DepGraph<'c> {
+    /// Renders the graph as an indented tree starting at the root. Dependencies that were
+    /// already expanded elsewhere in the tree are marked with `(*)` instead of being repeated.
+    pub fn render_tree_to<W: Write>(mut self, output: &mut W, color: bool) -> CliResult<()> {
+        debugln!("exec=render_tree_to;");
+        self.prepare();
+        let mut expanded = vec![false; self.nodes.len()];
+        expanded[0] = true;
+        try!(self.write_tree_label(output, 0, color));
+        try!(writeln!(output, ""));
+        try!(self.write_subtree(output, 0, "", &mut expanded, color));
+        Ok(())
+    }
+
+    fn write_subtree<W: Write>(&self,
+                               w: &mut W,
+                               id: Nd,
+                               prefix: &str,
+                               expanded: &mut [bool],
+                               color: bool)
+                               -> io::Result<()> {
+        let children = self.children(id);
+        for (i, &child) in children.iter().enumerate() {
+            let last = i == children.len() - 1;
+            try!(write!(w, "{}{}", prefix, if last { "└── " } else { "├── " }));
+            try!(self.write_tree_label(w, child, color));
+
+            if expanded[child] {
+                if self.edges.iter().any(|e| e.0 == child) {
+                    try!(write!(w, " (*)"));
+                }
+                try!(writeln!(w, ""));
+                continue;
+            }
+            try!(writeln!(w, ""));
+
+            expanded[child] = true;
+            let prefix = format!("{}{}", prefix, if last { "    " } else { "│   " });
+            try!(self.write_subtree(w, child, &prefix, expanded, color));
+        }
+        Ok(())
+    }
+
+    fn write_tree_label<W: Write>(&self, w: &mut W, id: Nd, color: bool) -> io::Result<()> {
+        let dep = &self.nodes[id];
+        let kind = dep.kind();
+        let label = match kind {
+            DepKind::Build => format!("{} v{}", dep.name, dep.versions()),
+            _ => format!("{} v{} ({})", dep.name, dep.versions(), kind),
+        };
+        if !color {
+            return write!(w, "{}", label);
+        }
+        match kind {
+            DepKind::Build => write!(w, "{}", label),
+            DepKind::Dev => write!(w, "{}", Format::Warning(label)),
+            DepKind::Optional => write!(w, "{}", Format::Good(label)),
+            DepKind::Unk => write!(w, "{}", Format::Dimmed(label)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use config::ConfigBuilder;
+    use graph::tests::graph;
+
+    #[test]
+    fn repeated_subtrees_are_marked() {
+        let cfg = ConfigBuilder::new().build();
+        let mut dg = graph(&cfg,
+                           &[("app", "a"), ("app", "b"), ("a", "c"), ("b", "a"), ("b", "c")]);
+        dg.nodes[2].is_build = false;
+        dg.nodes[2].is_dev = true;
+        let mut out = vec![];
+        dg.render_tree_to(&mut out, false).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(),
+                   "app v1.0.0\n\
+                    ├── a v1.0.0\n\
+                    │   └── c v1.0.0\n\
+                    └── b v1.0.0 (dev)\n\
+                    \x20   ├── a v1.0.0 (*)\n\
+                    \x20   └── c v1.0.0\n");
+    }
+}
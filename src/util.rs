@@ -1,11 +1,15 @@
+use std::cmp::Ordering;
 use std::env;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use std::fs::{self, File};
-use std::io::Read;
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+use std::thread;
 
+use rustc_serialize::json::Json;
 use toml::{self, Table};
 
-use error::{CliErrorKind, CliResult};
+use error::{CliError, CliErrorKind, CliResult};
 
 pub fn toml_from_file<P: AsRef<Path>>(p: P) -> CliResult<Box<Table>> {
     debugln!("executing; from_file; file={:?}", p.as_ref());
@@ -38,6 +42,178 @@ pub fn toml_from_file<P: AsRef<Path>>(p: P) -> CliResult<Box<Table>> {
     Err(From::from(CliErrorKind::Generic(error_str)))
 }
 
+/// Reads a JSON document from `file`, or from stdin when `file` is `-`.
+pub fn json_from_file(file: &str) -> CliResult<Json> {
+    debugln!("executing; json_from_file; file={:?}", file);
+    let mut s = String::new();
+    if file == "-" {
+        let stdin = io::stdin();
+        try!(stdin.lock().read_to_string(&mut s));
+    } else {
+        let mut f = try!(File::open(file));
+        try!(f.read_to_string(&mut s));
+    }
+
+    Json::from_str(&s).map_err(|e| {
+        From::from(CliErrorKind::Generic(format!("could not parse input as JSON\n{:?}: {}",
+                                                 file,
+                                                 e)))
+    })
+}
+
+/// Returns the directories holding the unpacked sources of registry crates, i.e.
+/// `$CARGO_HOME/registry/src/*`.
+pub fn registry_src_dirs() -> Vec<PathBuf> {
+    let cargo_home = env::var_os("CARGO_HOME")
+                         .map(PathBuf::from)
+                         .or_else(|| env::home_dir().map(|h| h.join(".cargo")));
+    let src = match cargo_home {
+        Some(home) => home.join("registry").join("src"),
+        None => return vec![],
+    };
+    fs::read_dir(src)
+        .map(|dirs| dirs.filter_map(|d| d.ok()).map(|d| d.path()).collect())
+        .unwrap_or_else(|_| vec![])
+}
+
+/// Finds the manifest of a crate unpacked in one of `dirs`, either as `<name>-<version>` (like
+/// the registry sources) or as `<name>` (like `cargo vendor`) when the version matches.
+pub fn find_crate_manifest(dirs: &[PathBuf], name: &str, ver: &str) -> Option<PathBuf> {
+    for dir in dirs {
+        let manifest = dir.join(format!("{}-{}", name, ver)).join("Cargo.toml");
+        if manifest.is_file() {
+            return Some(manifest);
+        }
+        let manifest = dir.join(name).join("Cargo.toml");
+        if manifest.is_file() {
+            let found_ver = toml_from_file(&manifest).ok().and_then(|t| {
+                t.get("package")
+                 .and_then(|p| p.lookup("version"))
+                 .and_then(toml::Value::as_str)
+                 .map(ToOwned::to_owned)
+            });
+            if found_ver.as_ref().map_or(false, |v| v == ver) {
+                return Some(manifest);
+            }
+        }
+    }
+    None
+}
+
+/// Escapes text for use in XML (or HTML) content and attribute values.
+pub fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+     .replace('<', "&lt;")
+     .replace('>', "&gt;")
+     .replace('"', "&quot;")
+     .replace('\'', "&apos;")
+}
+
+/// Returns whether `name` matches `pattern`, in which `*` stands for any run of characters.
+pub fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let parts = pattern.split('*').collect::<Vec<_>>();
+    if parts.len() == 1 {
+        return pattern == name;
+    }
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if name.len() < first.len() + last.len() || !name.starts_with(first) ||
+       !name.ends_with(last) {
+        return false;
+    }
+    let mut rest = &name[first.len()..name.len() - last.len()];
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+/// Compares two versions the way semver orders them: component by component as numbers (so
+/// `0.9.0` comes before `0.10.0`), with a pre-release (ex. `1.0.0-beta.2`) coming before its
+/// release. Build metadata is ignored.
+pub fn cmp_versions(l: &str, r: &str) -> Ordering {
+    fn split(ver: &str) -> (&str, Option<&str>) {
+        let ver = ver.splitn(2, '+').next().unwrap_or(ver);
+        let mut parts = ver.splitn(2, '-');
+        (parts.next().unwrap_or(""), parts.next())
+    }
+
+    // Numeric identifiers come before alphanumeric ones, and a shorter list before a longer
+    // one it starts
+    fn cmp_ids(l: &str, r: &str) -> Ordering {
+        let (mut l, mut r) = (l.split('.'), r.split('.'));
+        loop {
+            let ord = match (l.next(), r.next()) {
+                (Some(l), Some(r)) => {
+                    match (l.parse::<u64>(), r.parse::<u64>()) {
+                        (Ok(l), Ok(r)) => l.cmp(&r),
+                        (Ok(_), Err(_)) => Ordering::Less,
+                        (Err(_), Ok(_)) => Ordering::Greater,
+                        (Err(_), Err(_)) => l.cmp(r),
+                    }
+                }
+                (Some(_), None) => return Ordering::Greater,
+                (None, Some(_)) => return Ordering::Less,
+                (None, None) => return Ordering::Equal,
+            };
+            if ord != Ordering::Equal {
+                return ord;
+            }
+        }
+    }
+
+    let ((l, l_pre), (r, r_pre)) = (split(l), split(r));
+    match cmp_ids(l, r) {
+        Ordering::Equal => {}
+        ord => return ord,
+    }
+    match (l_pre, r_pre) {
+        (Some(l), Some(r)) => cmp_ids(l, r),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+/// Returns `path` relative to the directory `base` (ex. `../core`), resolving `.` and `..`
+/// without looking at the file system.
+pub fn relative_path(path: &Path, base: &Path) -> String {
+    fn normalize(path: &Path) -> Vec<Component> {
+        let mut parts = vec![];
+        for part in path.components() {
+            match part {
+                Component::CurDir => {}
+                Component::ParentDir if parts.last().map_or(false, |p| {
+                    match *p {
+                        Component::Normal(_) => true,
+                        _ => false,
+                    }
+                }) => {
+                    parts.pop();
+                }
+                _ => parts.push(part),
+            }
+        }
+        parts
+    }
+
+    let (path, base) = (normalize(path), normalize(base));
+    let common = path.iter().zip(base.iter()).take_while(|&(p, b)| p == b).count();
+    let mut relative = PathBuf::new();
+    for _ in common..base.len() {
+        relative.push("..");
+    }
+    for part in &path[common..] {
+        relative.push(part.as_os_str());
+    }
+    if relative.as_os_str().is_empty() {
+        return ".".to_owned();
+    }
+    relative.display().to_string()
+}
+
 pub fn find_manifest_file(file: &str) -> CliResult<PathBuf> {
     let mut pwd = try!(env::current_dir());
 
@@ -63,3 +239,112 @@ pub fn find_manifest_file(file: &str) -> CliResult<PathBuf> {
                                                  file,
                                                  pwd.display()))))
 }
+
+/// Whether stdout is a terminal, in which case the output can be colored.
+#[cfg(feature = "color")]
+pub fn stdout_is_tty() -> bool {
+    ::atty::is(::atty::Stream::Stdout)
+}
+
+#[cfg(not(feature = "color"))]
+pub fn stdout_is_tty() -> bool {
+    false
+}
+
+/// Pipes a DOT graph into GraphViz `dot` to render an image to `file`, using the file's
+/// extension as the output format.
+pub fn render_image(dot: &[u8], file: &str) -> CliResult<()> {
+    debugln!("executing; render_image; file={:?}", file);
+    run_dot("dot", dot, file)
+}
+
+fn run_dot(program: &str, dot: &[u8], file: &str) -> CliResult<()> {
+    let format = Path::new(file).extension().and_then(|e| e.to_str()).unwrap_or("png");
+    let mut child = try!(Command::new(program)
+                             .arg(format!("-T{}", format.to_lowercase()))
+                             .arg("-o")
+                             .arg(file)
+                             .stdin(Stdio::piped())
+                             .stdout(Stdio::null())
+                             .stderr(Stdio::piped())
+                             .spawn()
+                             .map_err(|e| if e.kind() == io::ErrorKind::NotFound {
+                                 CliError::from(CliErrorKind::DotNotFound)
+                             } else {
+                                 CliError::from(e)
+                             }));
+    // The input is written from another thread, or `dot` filling up the stderr pipe while it's
+    // still being fed would block both processes
+    let mut stdin = child.stdin.take().expect("failed to open stdin of `dot`");
+    let dot = dot.to_vec();
+    let writer = thread::spawn(move || stdin.write_all(&dot));
+
+    let out = try!(child.wait_with_output());
+    // `dot` may exit before reading all of its input (ex. on a syntax error), so a failed
+    // write is only reported if `dot` itself didn't fail
+    let written = writer.join().expect("failed to write to stdin of `dot`");
+    if !out.status.success() {
+        return Err(From::from(CliErrorKind::DotFailed(format!("`dot` failed ({}):\n{}",
+                                                             out.status,
+                                                             String::from_utf8_lossy(&out.stderr)
+                                                                 .trim()))));
+    }
+    try!(written);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+    use std::path::Path;
+
+    use error::CliErrorKind;
+    use super::{cmp_versions, relative_path, run_dot};
+
+    #[test]
+    fn versions_compare_as_semver() {
+        let mut vers = vec!["0.10.0", "0.9.0", "1.0.0", "1.0.0-beta.11", "1.0.0-beta.2",
+                            "1.0.0-alpha", "0.9.10"];
+        vers.sort_by(|l, r| cmp_versions(l, r));
+        assert_eq!(vers,
+                   ["0.9.0", "0.9.10", "0.10.0", "1.0.0-alpha", "1.0.0-beta.2",
+                    "1.0.0-beta.11", "1.0.0"]);
+        assert_eq!(cmp_versions("1.0.0+build.1", "1.0.0"), Ordering::Equal);
+    }
+
+    #[test]
+    fn relative_paths_resolve_dots() {
+        let base = Path::new("/ws/app");
+        assert_eq!(relative_path(Path::new("/ws/core"), base), "../core");
+        assert_eq!(relative_path(&base.join("../core/"), base), "../core");
+        assert_eq!(relative_path(&base.join("./libs/./util"), base), "libs/util");
+        assert_eq!(relative_path(base, base), ".");
+    }
+
+    #[test]
+    fn missing_dot_is_reported() {
+        match run_dot("cargo-graph-no-such-dot", b"digraph {}", "out.png") {
+            Err(ref e) => match e.kind {
+                CliErrorKind::DotNotFound => (),
+                ref kind => panic!("unexpected error: {:?}", kind),
+            },
+            Ok(()) => panic!("rendered without `dot`"),
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn failing_dot_is_reported_even_when_it_skips_its_input() {
+        // `false` exits without reading anything, like `dot` on a bad argument
+        let dot = vec![b' '; 1 << 20];
+        match run_dot("false", &dot, "out.png") {
+            Err(ref e) => match e.kind {
+                CliErrorKind::DotFailed(ref msg) => {
+                    assert!(msg.starts_with("`dot` failed"), "{}", msg)
+                }
+                ref kind => panic!("unexpected error: {:?}", kind),
+            },
+            Ok(()) => panic!("`false` rendered an image"),
+        }
+    }
+}
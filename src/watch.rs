@@ -0,0 +1,104 @@
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use error::CliResult;
+use fmt::Format;
+
+/// How often the watched files are checked for changes, in milliseconds.
+const POLL_INTERVAL: u64 = 500;
+/// How long the files must stay unchanged before regenerating, in milliseconds. Editors and
+/// `cargo update` may write several times in a row, and this waits for them to finish.
+const DEBOUNCE: u64 = 300;
+
+fn modified(files: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    files.iter().map(|f| fs::metadata(f).and_then(|m| m.modified()).ok()).collect()
+}
+
+fn report(result: CliResult<()>) {
+    match result {
+        Ok(()) => wlnerr!("{} regenerated the graph", Format::Good("watch:")),
+        // The files may be briefly invalid while they're being edited, so keep watching
+        Err(e) => wlnerr!("{}", e),
+    }
+}
+
+/// Checks `files` until they stay unchanged for `debounce`, starting from their `current`
+/// modification times, and returns the times they settled on.
+fn settle(files: &[PathBuf],
+          mut current: Vec<Option<SystemTime>>,
+          debounce: Duration)
+          -> Vec<Option<SystemTime>> {
+    loop {
+        thread::sleep(debounce);
+        let settled = modified(files);
+        if settled == current {
+            return current;
+        }
+        current = settled;
+    }
+}
+
+/// Calls `regenerate` with `files`, to which it may add more files to watch, and returns the
+/// watched files with their modification times.
+fn run<F>(files: &[PathBuf], regenerate: &mut F) -> (Vec<PathBuf>, Vec<Option<SystemTime>>)
+    where F: FnMut(&mut Vec<PathBuf>) -> CliResult<()>
+{
+    // The times are read first so a change made while regenerating isn't missed
+    let mut last = modified(files);
+    let mut watched = files.to_vec();
+    report(regenerate(&mut watched));
+    last.extend(modified(&watched[files.len()..]));
+    (watched, last)
+}
+
+/// Calls `regenerate` now and again every time one of `files` changes, until the process is
+/// killed. `regenerate` may add more files to watch (ex. the manifests of path crates), which
+/// are watched until it's called again.
+pub fn watch<F>(files: &[PathBuf], mut regenerate: F) -> !
+    where F: FnMut(&mut Vec<PathBuf>) -> CliResult<()>
+{
+    let (mut watched, mut last) = run(files, &mut regenerate);
+    loop {
+        thread::sleep(Duration::from_millis(POLL_INTERVAL));
+        let current = modified(&watched);
+        if current == last {
+            continue;
+        }
+        settle(&watched, current, Duration::from_millis(DEBOUNCE));
+        debugln!("watch; files changed");
+        let (w, l) = run(files, &mut regenerate);
+        watched = w;
+        last = l;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs::File;
+    use std::io::Write;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use super::{modified, settle};
+
+    #[test]
+    fn settle_waits_for_the_writes_to_stop() {
+        let file = env::temp_dir().join("cargo-graph-watched");
+        File::create(&file).unwrap();
+        let files = vec![file.clone()];
+        let start = Instant::now();
+        let writer = thread::spawn(move || {
+            for i in 0..4 {
+                thread::sleep(Duration::from_millis(100));
+                File::create(&file).unwrap().write_all(&[i]).unwrap();
+            }
+        });
+        let settled = settle(&files, modified(&files), Duration::from_millis(250));
+        writer.join().unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(400));
+        assert_eq!(settled, modified(&files));
+    }
+}
@@ -0,0 +1,196 @@
+use std::io::{self, Write};
+
+use error::CliResult;
+use graph::DepGraph;
+use util::xml_escape;
+
+impl<'c> DepGraph<'c> {
+    /// Renders the graph as GraphML, keeping each crate's name, version, kind, and source, and
+    /// each edge's kind, as attributes.
+    pub fn render_graphml_to<W: Write>(mut self, output: &mut W) -> CliResult<()> {
+        debugln!("exec=render_graphml_to;");
+        self.prepare();
+        try!(writeln!(output, r#"<?xml version="1.0" encoding="UTF-8"?>"#));
+        try!(writeln!(output,
+                      r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#));
+        for &(id, what, ty) in &[("name", "node", "string"),
+                                 ("version", "node", "string"),
+                                 ("kind", "node", "string"),
+                                 ("source", "node", "string"),
+                                 ("duplicate", "node", "boolean"),
+                                 ("edge_kind", "edge", "string")] {
+            let name = if id == "edge_kind" { "kind" } else { id };
+            try!(writeln!(output,
+                          r#"  <key id="{}" for="{}" attr.name="{}" attr.type="{}"/>"#,
+                          id,
+                          what,
+                          name,
+                          ty));
+        }
+        try!(writeln!(output, r#"  <graph id="dependencies" edgedefault="directed">"#));
+        for (i, dep) in self.nodes.iter().enumerate() {
+            try!(writeln!(output, r#"    <node id="{}">"#, xml_escape(&self.node_id(i))));
+            try!(write_data(output, "name", &dep.name));
+            try!(write_data(output, "version", &dep.versions()));
+            try!(write_data(output, "kind", &dep.kind().to_string()));
+            try!(write_data(output, "source", dep.source.as_ref().map_or("", |s| &**s)));
+            try!(write_data(output, "duplicate", &self.is_duplicate(i).to_string()));
+            try!(writeln!(output, "    </node>"));
+        }
+        for (i, ed) in self.edges.iter().enumerate() {
+            try!(writeln!(output,
+                          r#"    <edge id="E{}" source="{}" target="{}">"#,
+                          i,
+                          xml_escape(&self.node_id(ed.0)),
+                          xml_escape(&self.node_id(ed.1))));
+            try!(write_data(output, "edge_kind", &ed.kind(&self).to_string()));
+            try!(writeln!(output, "    </edge>"));
+        }
+        try!(writeln!(output, "  </graph>"));
+        try!(writeln!(output, "</graphml>"));
+        Ok(())
+    }
+
+    /// Renders the graph as GEXF, with the same attributes as the GraphML output.
+    pub fn render_gexf_to<W: Write>(mut self, output: &mut W) -> CliResult<()> {
+        debugln!("exec=render_gexf_to;");
+        self.prepare();
+        try!(writeln!(output, r#"<?xml version="1.0" encoding="UTF-8"?>"#));
+        try!(writeln!(output,
+                      r#"<gexf xmlns="http://www.gexf.net/1.2draft" version="1.2">"#));
+        try!(writeln!(output, r#"  <graph mode="static" defaultedgetype="directed">"#));
+        try!(writeln!(output, r#"    <attributes class="node">"#));
+        for (i, &(title, ty)) in [("name", "string"),
+                                  ("version", "string"),
+                                  ("kind", "string"),
+                                  ("source", "string"),
+                                  ("duplicate", "boolean")]
+                                     .iter()
+                                     .enumerate() {
+            try!(writeln!(output,
+                          r#"      <attribute id="{}" title="{}" type="{}"/>"#,
+                          i,
+                          title,
+                          ty));
+        }
+        try!(writeln!(output, "    </attributes>"));
+        try!(writeln!(output, r#"    <attributes class="edge">"#));
+        try!(writeln!(output, r#"      <attribute id="0" title="kind" type="string"/>"#));
+        try!(writeln!(output, "    </attributes>"));
+
+        try!(writeln!(output, "    <nodes>"));
+        for (i, dep) in self.nodes.iter().enumerate() {
+            try!(writeln!(output,
+                          r#"      <node id="{}" label="{}">"#,
+                          xml_escape(&self.node_id(i)),
+                          xml_escape(&dep.name)));
+            try!(writeln!(output, "        <attvalues>"));
+            try!(write_attvalue(output, 0, &dep.name));
+            try!(write_attvalue(output, 1, &dep.versions()));
+            try!(write_attvalue(output, 2, &dep.kind().to_string()));
+            try!(write_attvalue(output, 3, dep.source.as_ref().map_or("", |s| &**s)));
+            try!(write_attvalue(output, 4, &self.is_duplicate(i).to_string()));
+            try!(writeln!(output, "        </attvalues>"));
+            try!(writeln!(output, "      </node>"));
+        }
+        try!(writeln!(output, "    </nodes>"));
+
+        try!(writeln!(output, "    <edges>"));
+        for (i, ed) in self.edges.iter().enumerate() {
+            try!(writeln!(output,
+                          r#"      <edge id="E{}" source="{}" target="{}">"#,
+                          i,
+                          xml_escape(&self.node_id(ed.0)),
+                          xml_escape(&self.node_id(ed.1))));
+            try!(writeln!(output, "        <attvalues>"));
+            try!(write_attvalue(output, 0, &ed.kind(&self).to_string()));
+            try!(writeln!(output, "        </attvalues>"));
+            try!(writeln!(output, "      </edge>"));
+        }
+        try!(writeln!(output, "    </edges>"));
+        try!(writeln!(output, "  </graph>"));
+        try!(writeln!(output, "</gexf>"));
+        Ok(())
+    }
+}
+
+fn write_data<W: Write>(w: &mut W, key: &str, value: &str) -> io::Result<()> {
+    writeln!(w, r#"      <data key="{}">{}</data>"#, key, xml_escape(value))
+}
+
+fn write_attvalue<W: Write>(w: &mut W, id: usize, value: &str) -> io::Result<()> {
+    writeln!(w,
+             r#"          <attvalue for="{}" value="{}"/>"#,
+             id,
+             xml_escape(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use config::ConfigBuilder;
+    use dep::DepKind;
+    use graph::DepGraph;
+    use graph::tests::graph;
+
+    fn sample(dg: &mut DepGraph) {
+        dg.nodes[2].is_build = false;
+        dg.nodes[2].is_dev = true;
+        dg.nodes[2].source = Some("git+https://example.com/a?rev=1&x=2".to_owned());
+        dg.edges[1].2 = DepKind::Dev;
+    }
+
+    #[test]
+    fn graphml_keeps_the_attributes() {
+        let cfg = ConfigBuilder::new().build();
+        let mut dg = graph(&cfg, &[("app", "a"), ("app", "a@2.0.0")]);
+        sample(&mut dg);
+        let mut out = vec![];
+        dg.render_graphml_to(&mut out).unwrap();
+        let xml = String::from_utf8(out).unwrap();
+        assert!(xml.contains(r#"  <key id="edge_kind" for="edge" attr.name="kind" attr.type="string"/>"#),
+                "{}",
+                xml);
+        assert!(xml.contains(r#"    <node id="a@2.0.0">
+      <data key="name">a</data>
+      <data key="version">2.0.0</data>
+      <data key="kind">dev</data>
+      <data key="source">git+https://example.com/a?rev=1&amp;x=2</data>
+      <data key="duplicate">true</data>
+    </node>"#),
+                "{}",
+                xml);
+        assert!(xml.contains(r#"    <edge id="E0" source="app@1.0.0" target="a@1.0.0">
+      <data key="edge_kind">normal</data>
+    </edge>"#),
+                "{}",
+                xml);
+    }
+
+    #[test]
+    fn gexf_keeps_the_attributes() {
+        let cfg = ConfigBuilder::new().build();
+        let mut dg = graph(&cfg, &[("app", "a"), ("app", "a@2.0.0")]);
+        sample(&mut dg);
+        let mut out = vec![];
+        dg.render_gexf_to(&mut out).unwrap();
+        let xml = String::from_utf8(out).unwrap();
+        assert!(xml.contains(r#"      <node id="a@2.0.0" label="a">
+        <attvalues>
+          <attvalue for="0" value="a"/>
+          <attvalue for="1" value="2.0.0"/>
+          <attvalue for="2" value="dev"/>
+          <attvalue for="3" value="git+https://example.com/a?rev=1&amp;x=2"/>
+          <attvalue for="4" value="true"/>
+        </attvalues>
+      </node>"#),
+                "{}",
+                xml);
+        assert!(xml.contains(r#"      <edge id="E1" source="app@1.0.0" target="a@2.0.0">
+        <attvalues>
+          <attvalue for="0" value="dev"/>
+        </attvalues>
+      </edge>"#),
+                "{}",
+                xml);
+    }
+}